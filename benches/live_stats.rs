@@ -0,0 +1,122 @@
+//! Compares converting and encoding the node stats for every live stats
+//! stream with doing it once and sharing the encoded snapshot via the
+//! broadcaster
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use prost::Message;
+
+use node_stats_service::grpc::{proto, FieldMask, SnapshotBroadcaster};
+use node_stats_service::stats::bandwidth::Bandwidth;
+use node_stats_service::stats::cgroup::{CgroupNetwork, CgroupStats};
+use node_stats_service::stats::cpu::{CpuStats, CpuUsage};
+use node_stats_service::stats::disk::DiskStats;
+use node_stats_service::stats::NodeStats;
+
+const CLIENTS: &[usize] = &[1, 10, 50];
+
+/// Node stats of an edge node with some containers
+fn node_stats() -> NodeStats {
+    NodeStats {
+        bandwidth: Arc::new(Bandwidth {
+            tx_bps: 800_000_000,
+            rx_bps: 120_000_000,
+            ..Default::default()
+        }),
+        interface_bandwidth: Arc::new(
+            (0..4)
+                .map(|i| {
+                    (
+                        format!("eth{}", i),
+                        Arc::new(Bandwidth {
+                            tx_bps: 200_000_000,
+                            rx_bps: 30_000_000,
+                            ..Default::default()
+                        }),
+                    )
+                })
+                .collect(),
+        ),
+        cpu: Some(Arc::new(CpuStats {
+            total: CpuUsage::default(),
+            cores: vec![CpuUsage::default(); 32],
+        })),
+        cgroups: Some(Arc::new(
+            (0..40)
+                .map(|i| CgroupStats {
+                    name: format!("system.slice/docker-{:064x}.scope", i),
+                    network: Some(CgroupNetwork::default()),
+                    ..Default::default()
+                })
+                .collect(),
+        )),
+        disks: Some(Arc::new(
+            (0..8)
+                .map(|i| DiskStats {
+                    name: format!("nvme{}n1", i),
+                    ..Default::default()
+                })
+                .collect(),
+        )),
+        custom: Some(Arc::new(
+            (0..16)
+                .map(|i| (format!("metric_{}", i), i as f64))
+                .collect(),
+        )),
+        ..Default::default()
+    }
+}
+
+fn fan_out(c: &mut Criterion) {
+    let node_stats = node_stats();
+    let mut group = c.benchmark_group("live_stats_fan_out");
+
+    for clients in CLIENTS {
+        group.bench_with_input(
+            BenchmarkId::new("convert_per_client", clients),
+            clients,
+            |b, &clients| {
+                b.iter(|| {
+                    for _ in 0..clients {
+                        let message = proto::NodeStats::from(&node_stats);
+                        let mut buf = Vec::with_capacity(message.encoded_len());
+                        message.encode(&mut buf).unwrap();
+                        black_box(buf);
+                    }
+                })
+            },
+        );
+
+        for (name, fields) in &[
+            ("shared_snapshot", vec![]),
+            (
+                "shared_snapshot_masked",
+                vec!["used_bandwidth".into(), "cpu".into()],
+            ),
+        ] {
+            group.bench_with_input(BenchmarkId::new(*name, clients), clients, |b, &clients| {
+                let broadcaster = SnapshotBroadcaster::new(&node_stats);
+                let field_mask = FieldMask::new(fields.clone()).unwrap();
+                let mut subscribers: Vec<_> = (0..clients)
+                    .map(|_| broadcaster.subscribe().unwrap())
+                    .collect();
+
+                b.iter(|| {
+                    broadcaster.publish(&node_stats);
+
+                    // the first stream encodes the snapshot for all of them
+                    for subscriber in &mut subscribers {
+                        let snapshot = subscriber.try_recv().unwrap();
+                        black_box(snapshot.encoded(&field_mask));
+                    }
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, fan_out);
+criterion_main!(benches);
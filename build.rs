@@ -1,5 +1,7 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // the server is written by hand to send the live stats pre-encoded
     tonic_build::configure()
+        .build_server(false)
         .compile(&["proto/nodestats/node_stats.proto"], &["proto/nodestats"])?;
 
     Ok(())
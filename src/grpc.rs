@@ -2,70 +2,778 @@ pub mod proto {
     tonic::include_proto!("nodestats");
 }
 
+mod deadband;
+mod field_mask;
+mod server;
+mod snapshots;
+
+use std::convert::TryFrom;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use log::info;
 
-use tokio::sync::mpsc;
+use prost::bytes::Bytes;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time;
 use tonic::{Request, Response, Status};
 
 use super::stats;
+use deadband::{Deadbands, UpdateFilter};
+use snapshots::{recv_latest, Snapshot};
 
-pub use proto::node_stats_service_server::NodeStatsServiceServer;
+pub use field_mask::FieldMask;
+pub use server::NodeStatsServiceServer;
+pub use snapshots::{NodeStatsSnapshot, SnapshotBroadcaster};
 
 pub struct NodeStatsService {
-    pub node_stats_provider: Arc<stats::NodeStatsProvider>,
+    node_stats_provider: Arc<stats::NodeStatsProvider>,
+    live_stats_intervals: LiveStatsIntervals,
+    snapshots: Arc<SnapshotBroadcaster>,
+}
+
+impl NodeStatsService {
+    /// Starts converting the node stats updates once for all live stats streams
+    pub fn new(
+        node_stats_provider: Arc<stats::NodeStatsProvider>,
+        live_stats_intervals: LiveStatsIntervals,
+    ) -> Self {
+        let snapshots = SnapshotBroadcaster::start(&node_stats_provider);
+
+        Self {
+            node_stats_provider,
+            live_stats_intervals,
+            snapshots,
+        }
+    }
 }
 
-#[tonic::async_trait]
-impl proto::node_stats_service_server::NodeStatsService for NodeStatsService {
-    type GetLiveStatsStream = mpsc::Receiver<Result<proto::NodeStats, Status>>;
+/// Bounds of the streaming intervals the clients can request
+#[derive(Debug, Clone)]
+pub struct LiveStatsIntervals {
+    /// Used if the client doesn't request an interval
+    pub default: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Heartbeats are meant to be longer than the intervals, so they have
+    /// their own upper bound
+    pub max_heartbeat: Duration,
+}
+
+impl LiveStatsIntervals {
+    fn clamp(&self, interval_millis: u64) -> Duration {
+        if interval_millis == 0 {
+            return self.default;
+        }
+
+        Duration::from_millis(interval_millis)
+            .max(self.min)
+            .min(self.max)
+    }
 
-    async fn get_live_stats(
+    /// None if the client doesn't request a heartbeat
+    fn clamp_heartbeat(&self, heartbeat_millis: u64) -> Option<Duration> {
+        if heartbeat_millis == 0 {
+            return None;
+        }
+
+        Some(
+            Duration::from_millis(heartbeat_millis)
+                .max(self.min)
+                .min(self.max_heartbeat),
+        )
+    }
+}
+
+/// The encoded NodeStats messages of a live stats stream
+pub type LiveStatsStream = mpsc::Receiver<Result<Bytes, Status>>;
+
+impl NodeStatsService {
+    pub async fn get_live_stats(
         &self,
         request: Request<proto::LiveNodeStatsRequest>,
-    ) -> Result<Response<Self::GetLiveStatsStream>, Status> {
-        let node_stats_provider = Arc::clone(&self.node_stats_provider);
-        let (mut tx, rx) = mpsc::channel(1);
-
-        tokio::spawn(async move {
-            info!(
-                "Starting live stats streaming for client: {:?}",
-                request.remote_addr()
-            );
-            let mut interval = time::interval(Duration::from_secs(1));
-            interval.tick().await; // the first tick completes immediately
-
-            loop {
-                let node_stats = node_stats_provider.current_node_stats();
-                let send_result = tx
-                    .send(Ok(proto::NodeStats::from(node_stats.as_ref())))
-                    .await;
-
-                if let Err(e) = send_result {
-                    info!("Failed to send live stats, stopping live stats streaming for client {:?}, SendError: {:?}", request.remote_addr(), e);
-                    break;
+    ) -> Result<Response<LiveStatsStream>, Status> {
+        let remote_addr = request.remote_addr();
+        let request = request.into_inner();
+        let mode = proto::StreamingMode::from_i32(request.mode)
+            .ok_or_else(|| Status::invalid_argument(format!("Unknown mode {}", request.mode)))?;
+        let field_mask =
+            FieldMask::new(request.fields).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let deadbands = Deadbands::new(request.deadbands)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let heartbeat = self
+            .live_stats_intervals
+            .clamp_heartbeat(request.heartbeat_millis);
+        let (tx, rx) = mpsc::channel(1);
+
+        match mode {
+            proto::StreamingMode::Interval => {
+                let update_interval = self.live_stats_intervals.clamp(request.interval_millis);
+                info!(
+                    "Starting live stats streaming for client: {:?} with interval {:?} and heartbeat {:?}",
+                    remote_addr, update_interval, heartbeat
+                );
+
+                // without deadbands and heartbeat the stats are sent every interval
+                let update_filter = if deadbands.is_empty() && heartbeat.is_none() {
+                    None
                 } else {
-                    info!("Sent live stats to client {:?}", request.remote_addr());
-                }
+                    Some(UpdateFilter::new(deadbands, heartbeat))
+                };
 
-                interval.tick().await;
+                // the stream starts with the latest snapshot, then follows
+                // the ticker shared by all streams with the same interval
+                let ticks = self.snapshots.subscribe_interval(update_interval);
+
+                tokio::spawn(stream_at_interval(
+                    self.snapshots.latest(),
+                    ticks,
+                    field_mask,
+                    update_filter,
+                    tx,
+                    remote_addr,
+                ));
             }
-        });
+            proto::StreamingMode::OnChange => {
+                // subscribed before taking the latest snapshot to not miss an update
+                let updates = self
+                    .snapshots
+                    .subscribe()
+                    .ok_or_else(|| Status::unavailable("The node stats aren't updated anymore"))?;
+                let min_spacing = Duration::from_millis(request.min_spacing_millis);
+                info!(
+                    "Starting live stats streaming for client: {:?} on change with min spacing {:?} and heartbeat {:?}",
+                    remote_addr, min_spacing, heartbeat
+                );
+
+                tokio::spawn(stream_changes(
+                    self.snapshots.latest(),
+                    updates,
+                    min_spacing,
+                    field_mask,
+                    UpdateFilter::new(deadbands, heartbeat),
+                    tx,
+                    remote_addr,
+                ));
+            }
+        }
 
         Ok(Response::new(rx))
     }
+
+    pub async fn get_stats(
+        &self,
+        request: Request<proto::NodeStatsRequest>,
+    ) -> Result<Response<proto::NodeStats>, Status> {
+        info!("Sending stats to client {:?}", request.remote_addr());
+        let node_stats = self.node_stats_provider.current_node_stats();
+
+        Ok(Response::new(proto::NodeStats::from(node_stats.as_ref())))
+    }
+}
+
+type LiveStatsSender = mpsc::Sender<Result<Bytes, Status>>;
+
+/// Sends the stats on every tick, the update filter can skip them
+async fn stream_at_interval(
+    initial_snapshot: Snapshot,
+    mut ticks: broadcast::Receiver<Snapshot>,
+    field_mask: FieldMask,
+    mut update_filter: Option<UpdateFilter>,
+    mut tx: LiveStatsSender,
+    remote_addr: Option<SocketAddr>,
+) {
+    let mut next_snapshot = Some(initial_snapshot);
+
+    while let Some(snapshot) = next_snapshot {
+        let send = match &mut update_filter {
+            Some(filter) => filter.should_send(&snapshot.metrics(), &field_mask, Instant::now()),
+            None => true,
+        };
+
+        if send && !send_live_stats(&mut tx, snapshot.encoded(&field_mask), remote_addr).await {
+            return;
+        }
+
+        next_snapshot = recv_latest(&mut ticks).await;
+    }
+}
+
+/// Sends the stats whenever they change and pass the update filter, updates
+/// in between the min spacing are coalesced into the latest stats. Without
+/// updates the latest stats are sent again once the heartbeat is due.
+async fn stream_changes(
+    initial_snapshot: Snapshot,
+    mut updates: broadcast::Receiver<Snapshot>,
+    min_spacing: Duration,
+    field_mask: FieldMask,
+    mut update_filter: UpdateFilter,
+    mut tx: LiveStatsSender,
+    remote_addr: Option<SocketAddr>,
+) {
+    let mut next_snapshot = Some(initial_snapshot);
+
+    while let Some(snapshot) = next_snapshot {
+        // updates of fields outside of the mask only change the timestamp
+        if update_filter.should_send(&snapshot.metrics(), &field_mask, Instant::now()) {
+            if !send_live_stats(&mut tx, snapshot.encoded(&field_mask), remote_addr).await {
+                return;
+            }
+
+            if min_spacing.as_nanos() > 0 {
+                time::delay_for(min_spacing).await;
+            }
+        }
+
+        next_snapshot = match update_filter.until_heartbeat(Instant::now()) {
+            Some(until_heartbeat) => time::timeout(until_heartbeat, recv_latest(&mut updates))
+                .await
+                .unwrap_or(Some(snapshot)),
+            None => recv_latest(&mut updates).await,
+        };
+    }
+
+    info!(
+        "Node stats subscription closed, stopping live stats streaming for client {:?}",
+        remote_addr
+    );
+}
+
+/// Returns false if the client went away
+async fn send_live_stats(
+    tx: &mut LiveStatsSender,
+    message: Bytes,
+    remote_addr: Option<SocketAddr>,
+) -> bool {
+    if let Err(e) = tx.send(Ok(message)).await {
+        info!("Failed to send live stats, stopping live stats streaming for client {:?}, SendError: {:?}", remote_addr, e);
+        false
+    } else {
+        info!("Sent live stats to client {:?}", remote_addr);
+        true
+    }
+}
+
+/// Milliseconds since the unix epoch, zero for times before it
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or_default()
 }
 
 impl From<&stats::NodeStats> for proto::NodeStats {
     fn from(node_stats: &stats::NodeStats) -> Self {
         proto::NodeStats {
-            used_bandwidth: Some(proto::Bandwidth {
-                tx_bps: node_stats.bandwidth.tx_bps,
-                rx_bps: node_stats.bandwidth.rx_bps,
+            used_bandwidth: Some(proto::Bandwidth::from(node_stats.bandwidth.as_ref())),
+            interface_bandwidth: node_stats
+                .interface_bandwidth
+                .iter()
+                .map(|(name, bandwidth)| (name.clone(), proto::Bandwidth::from(bandwidth.as_ref())))
+                .collect(),
+            cpu: node_stats
+                .cpu
+                .as_ref()
+                .map(|cpu| proto::CpuStats::from(cpu.as_ref())),
+            memory: node_stats
+                .memory
+                .as_ref()
+                .map(|memory| proto::MemoryStats::from(memory.as_ref())),
+            load: node_stats
+                .load
+                .as_ref()
+                .map(|load| proto::LoadStats::from(load.as_ref())),
+            cgroups: node_stats
+                .cgroups
+                .as_ref()
+                .map(|cgroups| cgroups.iter().map(proto::CgroupStats::from).collect())
+                .unwrap_or_default(),
+            tcp_connections: node_stats
+                .tcp_connections
+                .as_ref()
+                .map(|counts| proto::TcpConnectionStats::from(counts.as_ref())),
+            filesystems: node_stats
+                .filesystems
+                .as_ref()
+                .map(|filesystems| {
+                    filesystems
+                        .iter()
+                        .map(proto::FilesystemStats::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            disks: node_stats
+                .disks
+                .as_ref()
+                .map(|disks| disks.iter().map(proto::DiskStats::from).collect())
+                .unwrap_or_default(),
+            thermal: node_stats
+                .thermal
+                .as_ref()
+                .map(|thermal| proto::ThermalStats::from(thermal.as_ref())),
+            custom: node_stats
+                .custom
+                .as_ref()
+                .map(|custom| {
+                    custom
+                        .iter()
+                        .map(|(name, value)| (name.clone(), *value))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            nginx: node_stats
+                .nginx
+                .as_ref()
+                .map(|nginx| proto::NginxStats::from(nginx.as_ref())),
+            timestamp_millis: node_stats.updated_at.map(unix_millis).unwrap_or_default(),
+            source_timestamp_millis: node_stats
+                .source_updated_at
+                .iter()
+                .map(|(name, updated_at)| (name.to_string(), unix_millis(*updated_at)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&stats::bandwidth::Bandwidth> for proto::Bandwidth {
+    fn from(bandwidth: &stats::bandwidth::Bandwidth) -> Self {
+        proto::Bandwidth {
+            tx_bps: bandwidth.tx_bps,
+            rx_bps: bandwidth.rx_bps,
+            capacity_bps: bandwidth.capacity_bps,
+            tx_utilization: bandwidth.tx_utilization,
+            rx_utilization: bandwidth.rx_utilization,
+            counter_reset: bandwidth.counter_reset,
+            counter_resets: bandwidth.counter_resets,
+            tx_ewma_bps: bandwidth.tx_ewma_bps,
+            rx_ewma_bps: bandwidth.rx_ewma_bps,
+            tx_window_mean_bps: bandwidth.tx_window_mean_bps,
+            rx_window_mean_bps: bandwidth.rx_window_mean_bps,
+            tx_window_max_bps: bandwidth.tx_window_max_bps,
+            rx_window_max_bps: bandwidth.rx_window_max_bps,
+            percentiles: Some(proto::BandwidthPercentiles::from(&bandwidth.percentiles)),
+            packet_rates: Some(proto::PacketRates::from(&bandwidth.packet_rates)),
+        }
+    }
+}
+
+impl From<&stats::bandwidth::PacketRates> for proto::PacketRates {
+    fn from(packet_rates: &stats::bandwidth::PacketRates) -> Self {
+        proto::PacketRates {
+            rx_packets: packet_rates.rx_packets,
+            tx_packets: packet_rates.tx_packets,
+            rx_errors: packet_rates.rx_errors,
+            tx_errors: packet_rates.tx_errors,
+            rx_dropped: packet_rates.rx_dropped,
+            tx_dropped: packet_rates.tx_dropped,
+            rx_fifo_errors: packet_rates.rx_fifo_errors,
+            tx_fifo_errors: packet_rates.tx_fifo_errors,
+            collisions: packet_rates.collisions,
+        }
+    }
+}
+
+impl From<&stats::bandwidth::BandwidthPercentiles> for proto::BandwidthPercentiles {
+    fn from(percentiles: &stats::bandwidth::BandwidthPercentiles) -> Self {
+        proto::BandwidthPercentiles {
+            one: Some(proto::WindowPercentiles::from(&percentiles.one)),
+            five: Some(proto::WindowPercentiles::from(&percentiles.five)),
+            fifteen: Some(proto::WindowPercentiles::from(&percentiles.fifteen)),
+        }
+    }
+}
+
+impl From<&stats::bandwidth::WindowPercentiles> for proto::WindowPercentiles {
+    fn from(percentiles: &stats::bandwidth::WindowPercentiles) -> Self {
+        proto::WindowPercentiles {
+            tx: Some(proto::RatePercentiles::from(&percentiles.tx)),
+            rx: Some(proto::RatePercentiles::from(&percentiles.rx)),
+        }
+    }
+}
+
+impl From<&stats::bandwidth::RatePercentiles> for proto::RatePercentiles {
+    fn from(percentiles: &stats::bandwidth::RatePercentiles) -> Self {
+        proto::RatePercentiles {
+            p50_bps: percentiles.p50_bps,
+            p95_bps: percentiles.p95_bps,
+            p99_bps: percentiles.p99_bps,
+            peak_bps: percentiles.peak_bps,
+        }
+    }
+}
+
+impl From<&stats::cpu::CpuStats> for proto::CpuStats {
+    fn from(cpu_stats: &stats::cpu::CpuStats) -> Self {
+        proto::CpuStats {
+            total: Some(proto::CpuUsage::from(&cpu_stats.total)),
+            cores: cpu_stats.cores.iter().map(proto::CpuUsage::from).collect(),
+        }
+    }
+}
+
+impl From<&stats::cpu::CpuUsage> for proto::CpuUsage {
+    fn from(cpu_usage: &stats::cpu::CpuUsage) -> Self {
+        proto::CpuUsage {
+            user: cpu_usage.user,
+            system: cpu_usage.system,
+            iowait: cpu_usage.iowait,
+            steal: cpu_usage.steal,
+        }
+    }
+}
+
+impl From<&stats::memory::MemoryStats> for proto::MemoryStats {
+    fn from(memory_stats: &stats::memory::MemoryStats) -> Self {
+        proto::MemoryStats {
+            total: memory_stats.total,
+            available: memory_stats.available,
+            cached: memory_stats.cached,
+            dirty: memory_stats.dirty,
+            swap_total: memory_stats.swap_total,
+            swap_free: memory_stats.swap_free,
+        }
+    }
+}
+
+impl From<&stats::cgroup::CgroupStats> for proto::CgroupStats {
+    fn from(cgroup_stats: &stats::cgroup::CgroupStats) -> Self {
+        proto::CgroupStats {
+            name: cgroup_stats.name.clone(),
+            cpu_usage: cgroup_stats.cpu_usage,
+            cpu_user: cgroup_stats.cpu_user,
+            cpu_system: cgroup_stats.cpu_system,
+            memory_current: cgroup_stats.memory_current,
+            network: cgroup_stats
+                .network
+                .as_ref()
+                .map(|network| proto::CgroupNetwork {
+                    rx_bps: network.rx_bps,
+                    tx_bps: network.tx_bps,
+                }),
+        }
+    }
+}
+
+impl From<&stats::tcp_connections::TcpStateCounts> for proto::TcpConnectionStats {
+    fn from(counts: &stats::tcp_connections::TcpStateCounts) -> Self {
+        proto::TcpConnectionStats {
+            established: counts.established,
+            syn_sent: counts.syn_sent,
+            syn_recv: counts.syn_recv,
+            fin_wait1: counts.fin_wait1,
+            fin_wait2: counts.fin_wait2,
+            time_wait: counts.time_wait,
+            close: counts.close,
+            close_wait: counts.close_wait,
+            last_ack: counts.last_ack,
+            listen: counts.listen,
+            closing: counts.closing,
+        }
+    }
+}
+
+impl From<&stats::filesystem::FilesystemStats> for proto::FilesystemStats {
+    fn from(filesystem_stats: &stats::filesystem::FilesystemStats) -> Self {
+        proto::FilesystemStats {
+            path: filesystem_stats.path.clone(),
+            total_bytes: filesystem_stats.total_bytes,
+            free_bytes: filesystem_stats.free_bytes,
+            available_bytes: filesystem_stats.available_bytes,
+            total_inodes: filesystem_stats.total_inodes,
+            free_inodes: filesystem_stats.free_inodes,
+            available_inodes: filesystem_stats.available_inodes,
+        }
+    }
+}
+
+impl From<&stats::disk::DiskStats> for proto::DiskStats {
+    fn from(disk_stats: &stats::disk::DiskStats) -> Self {
+        proto::DiskStats {
+            name: disk_stats.name.clone(),
+            read_bytes_per_second: disk_stats.read_bytes_per_second,
+            write_bytes_per_second: disk_stats.write_bytes_per_second,
+            read_iops: disk_stats.read_iops,
+            write_iops: disk_stats.write_iops,
+            utilization: disk_stats.utilization,
+        }
+    }
+}
+
+impl From<&stats::thermal::ThermalStats> for proto::ThermalStats {
+    fn from(thermal_stats: &stats::thermal::ThermalStats) -> Self {
+        proto::ThermalStats {
+            temperatures: thermal_stats
+                .temperatures
+                .iter()
+                .map(|temperature| proto::Temperature {
+                    label: temperature.label.clone(),
+                    celsius: temperature.celsius,
+                    warning: temperature.warning,
+                    critical: temperature.critical,
+                })
+                .collect(),
+            core_throttle_count: thermal_stats.throttle_counts.core,
+            package_throttle_count: thermal_stats.throttle_counts.package,
+            warning: thermal_stats.warning,
+            critical: thermal_stats.critical,
+        }
+    }
+}
+
+impl From<&stats::nginx::NginxStats> for proto::NginxStats {
+    fn from(nginx_stats: &stats::nginx::NginxStats) -> Self {
+        proto::NginxStats {
+            active_connections: nginx_stats.active_connections,
+            reading: nginx_stats.reading,
+            writing: nginx_stats.writing,
+            waiting: nginx_stats.waiting,
+            accepts_per_second: nginx_stats.accepts_per_second,
+            handled_per_second: nginx_stats.handled_per_second,
+            requests_per_second: nginx_stats.requests_per_second,
+        }
+    }
+}
+
+impl From<&stats::load::LoadStats> for proto::LoadStats {
+    fn from(load_stats: &stats::load::LoadStats) -> Self {
+        proto::LoadStats {
+            load_average: Some(proto::LoadAverage {
+                one: load_stats.load_average.one,
+                five: load_stats.load_average.five,
+                fifteen: load_stats.load_average.fifteen,
+                runnable_tasks: load_stats.load_average.runnable_tasks,
+                total_tasks: load_stats.load_average.total_tasks,
             }),
+            cpu_pressure: load_stats.cpu_pressure.as_ref().map(proto::Pressure::from),
+            memory_pressure: load_stats
+                .memory_pressure
+                .as_ref()
+                .map(proto::Pressure::from),
+            io_pressure: load_stats.io_pressure.as_ref().map(proto::Pressure::from),
+        }
+    }
+}
+
+impl From<&stats::load::Pressure> for proto::Pressure {
+    fn from(pressure: &stats::load::Pressure) -> Self {
+        proto::Pressure {
+            some: Some(proto::PressureValues::from(&pressure.some)),
+            full: pressure.full.as_ref().map(proto::PressureValues::from),
+        }
+    }
+}
+
+impl From<&stats::load::PressureValues> for proto::PressureValues {
+    fn from(values: &stats::load::PressureValues) -> Self {
+        proto::PressureValues {
+            avg10: values.avg10,
+            avg60: values.avg60,
+            avg300: values.avg300,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::Message;
+
+    fn intervals() -> LiveStatsIntervals {
+        LiveStatsIntervals {
+            default: Duration::from_secs(1),
+            min: Duration::from_millis(100),
+            max: Duration::from_secs(60),
+            max_heartbeat: Duration::from_secs(600),
         }
     }
+
+    fn snapshot(node_stats: stats::NodeStats) -> Snapshot {
+        Arc::new(NodeStatsSnapshot::from(&node_stats))
+    }
+
+    async fn recv_node_stats(stream: &mut LiveStatsStream) -> proto::NodeStats {
+        let message = stream.recv().await.unwrap().unwrap();
+        proto::NodeStats::decode(message).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_stats() {
+        let service =
+            NodeStatsService::new(Arc::new(stats::NodeStatsProvider::new(vec![])), intervals());
+
+        let node_stats = service
+            .get_stats(Request::new(proto::NodeStatsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(0, node_stats.timestamp_millis);
+        assert_eq!(0, node_stats.used_bandwidth.unwrap().tx_bps);
+    }
+
+    #[tokio::test]
+    async fn test_get_live_stats() {
+        let service =
+            NodeStatsService::new(Arc::new(stats::NodeStatsProvider::new(vec![])), intervals());
+
+        let mut stream = service
+            .get_live_stats(Request::new(proto::LiveNodeStatsRequest {
+                interval_millis: 10,
+                fields: vec!["cpu".into()],
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let node_stats = recv_node_stats(&mut stream).await;
+        assert_eq!(None, node_stats.used_bandwidth);
+
+        let invalid_request = service
+            .get_live_stats(Request::new(proto::LiveNodeStatsRequest {
+                fields: vec!["gpu".into()],
+                ..Default::default()
+            }))
+            .await;
+        assert_eq!(
+            tonic::Code::InvalidArgument,
+            invalid_request.err().unwrap().code()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_changes() {
+        let (update_sender, updates) = broadcast::channel(16);
+        let (tx, mut rx) = mpsc::channel(1);
+
+        tokio::spawn(stream_changes(
+            snapshot(Default::default()),
+            updates,
+            Duration::from_millis(0),
+            FieldMask::new(vec!["cpu".into()]).unwrap(),
+            UpdateFilter::new(Default::default(), None),
+            tx,
+            None,
+        ));
+
+        let node_stats = recv_node_stats(&mut rx).await;
+        assert_eq!(None, node_stats.cpu);
+
+        // changes outside of the field mask are skipped
+        let bandwidth_update = stats::NodeStats {
+            bandwidth: Arc::new(stats::bandwidth::Bandwidth {
+                tx_bps: 1,
+                rx_bps: 1,
+                ..Default::default()
+            }),
+            updated_at: Some(SystemTime::now()),
+            ..Default::default()
+        };
+        update_sender.send(snapshot(bandwidth_update)).unwrap();
+        time::delay_for(Duration::from_millis(10)).await;
+
+        let cpu_update = stats::NodeStats {
+            cpu: Some(Arc::new(Default::default())),
+            ..Default::default()
+        };
+        update_sender.send(snapshot(cpu_update)).unwrap();
+
+        let node_stats = recv_node_stats(&mut rx).await;
+        assert!(node_stats.cpu.is_some());
+        assert_eq!(None, node_stats.used_bandwidth);
+
+        drop(update_sender);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_changes_heartbeat() {
+        let (update_sender, updates) = broadcast::channel(16);
+        let (tx, mut rx) = mpsc::channel(1);
+        let deadbands = Deadbands::new(vec![proto::Deadband {
+            metric: "used_bandwidth.tx_bps".into(),
+            absolute: 100.0,
+            relative: 0.0,
+        }])
+        .unwrap();
+
+        tokio::spawn(stream_changes(
+            snapshot(Default::default()),
+            updates,
+            Duration::from_millis(0),
+            FieldMask::new(vec!["used_bandwidth".into()]).unwrap(),
+            UpdateFilter::new(deadbands, Some(Duration::from_millis(100))),
+            tx,
+            None,
+        ));
+
+        let node_stats = recv_node_stats(&mut rx).await;
+        assert_eq!(0, node_stats.used_bandwidth.unwrap().tx_bps);
+
+        // within the deadband, only sent with the heartbeat
+        let start = Instant::now();
+        let bandwidth_update = stats::NodeStats {
+            bandwidth: Arc::new(stats::bandwidth::Bandwidth {
+                tx_bps: 50,
+                rx_bps: 0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        update_sender.send(snapshot(bandwidth_update)).unwrap();
+
+        let node_stats = recv_node_stats(&mut rx).await;
+        assert_eq!(50, node_stats.used_bandwidth.unwrap().tx_bps);
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        let bandwidth_update = stats::NodeStats {
+            bandwidth: Arc::new(stats::bandwidth::Bandwidth {
+                tx_bps: 500,
+                rx_bps: 0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        update_sender.send(snapshot(bandwidth_update)).unwrap();
+
+        let node_stats = recv_node_stats(&mut rx).await;
+        assert_eq!(500, node_stats.used_bandwidth.unwrap().tx_bps);
+    }
+
+    #[test]
+    fn test_clamp_interval() {
+        let intervals = intervals();
+
+        assert_eq!(Duration::from_secs(1), intervals.clamp(0));
+        assert_eq!(Duration::from_millis(250), intervals.clamp(250));
+        assert_eq!(Duration::from_millis(100), intervals.clamp(1));
+        assert_eq!(Duration::from_secs(60), intervals.clamp(3_600_000));
+    }
+
+    #[test]
+    fn test_clamp_heartbeat() {
+        let intervals = intervals();
+
+        assert_eq!(None, intervals.clamp_heartbeat(0));
+        assert_eq!(
+            Some(Duration::from_millis(100)),
+            intervals.clamp_heartbeat(1)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(300)),
+            intervals.clamp_heartbeat(300_000)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(600)),
+            intervals.clamp_heartbeat(3_600_000)
+        );
+    }
+
+    #[test]
+    fn test_unix_millis() {
+        assert_eq!(1500, unix_millis(UNIX_EPOCH + Duration::from_millis(1500)));
+        assert_eq!(0, unix_millis(UNIX_EPOCH - Duration::from_secs(1)));
+    }
 }
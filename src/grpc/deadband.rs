@@ -0,0 +1,840 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use regex::Regex;
+
+use super::field_mask::{FieldMask, FIELDS};
+use super::proto;
+
+/// The numeric values of a NodeStats message by their dot separated path,
+/// e.g. `load.load_average.one` or `disks.sda.utilization`. Dots, stars and
+/// backslashes in names are escaped with a backslash, like in
+/// `cgroups.system\.slice.cpu_usage`.
+pub type Metrics = BTreeMap<String, f64>;
+
+/// Matches a single path segment for a `*` of a deadband metric
+const SEGMENT_PATTERN: &str = r"(?:[^.\\]|\\.)*";
+
+/// Ignored changes of the metrics matching a path
+#[derive(Debug)]
+struct Deadband {
+    metric: String,
+    pattern: Regex,
+    absolute: f64,
+    relative: f64,
+}
+
+/// The deadbands requested by a client, metrics without a deadband are
+/// sent on every change
+#[derive(Debug, Default)]
+pub struct Deadbands {
+    deadbands: Vec<Deadband>,
+}
+
+impl Deadbands {
+    /// The metric of a deadband is a path prefix, `*` matches any part of
+    /// a path segment like in `disks.*.utilization`
+    pub fn new(deadbands: Vec<proto::Deadband>) -> anyhow::Result<Self> {
+        let deadbands = deadbands
+            .into_iter()
+            .map(|deadband| {
+                let field = deadband.metric.split('.').next().unwrap_or_default();
+                if field != "*" && !FIELDS.contains(&field) {
+                    return Err(anyhow!(
+                        "Unknown deadband metric {}, expected it to start with one of {}",
+                        deadband.metric,
+                        FIELDS.join(", ")
+                    ));
+                }
+
+                for value in &[deadband.absolute, deadband.relative] {
+                    if !value.is_finite() || *value < 0.0 {
+                        return Err(anyhow!(
+                            "Invalid deadband {} of metric {}, expected a positive number",
+                            value,
+                            deadband.metric
+                        ));
+                    }
+                }
+
+                Ok(Deadband {
+                    pattern: metric_pattern(&deadband.metric)?,
+                    metric: deadband.metric,
+                    absolute: deadband.absolute,
+                    relative: deadband.relative,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Deadbands { deadbands })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deadbands.is_empty()
+    }
+
+    /// The ignored change of the value, the longest matching deadband wins
+    fn band(&self, path: &str, last_value: f64) -> f64 {
+        self.deadbands
+            .iter()
+            .filter(|deadband| deadband.pattern.is_match(path))
+            .max_by_key(|deadband| deadband.metric.len())
+            .map(|deadband| deadband.absolute.max(deadband.relative * last_value.abs()))
+            .unwrap_or(0.0)
+    }
+
+    /// Whether any metric selected by the field mask changed by more than
+    /// its deadband or appeared or disappeared since the last sent metrics
+    pub fn moved(&self, last: &Metrics, current: &Metrics, field_mask: &FieldMask) -> bool {
+        let selected = |metrics: &'_ Metrics| {
+            metrics
+                .iter()
+                .filter(move |(path, _)| field_mask.includes_metric(path))
+                .count()
+        };
+
+        selected(last) != selected(current)
+            || current
+                .iter()
+                .filter(|(path, _)| field_mask.includes_metric(path))
+                .any(|(path, value)| match last.get(path) {
+                    Some(last_value) => (value - last_value).abs() > self.band(path, *last_value),
+                    None => true,
+                })
+    }
+}
+
+/// Matches the metric and the paths below it, escaped characters are
+/// matched as they appear in the paths
+fn metric_pattern(metric: &str) -> anyhow::Result<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = metric.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("Trailing backslash in deadband metric {}", metric))?;
+                pattern.push_str(&regex::escape(&format!("\\{}", escaped)));
+            }
+            '*' => pattern.push_str(SEGMENT_PATTERN),
+            c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push_str(r"(?:\..*)?$");
+
+    Ok(Regex::new(&pattern)?)
+}
+
+/// Decides which live stats are sent to a client, stats that didn't move
+/// out of their deadbands are skipped until the heartbeat is due
+#[derive(Debug)]
+pub struct UpdateFilter {
+    deadbands: Deadbands,
+    heartbeat: Option<Duration>,
+    last_sent: Option<(Instant, Arc<Metrics>)>,
+}
+
+impl UpdateFilter {
+    pub fn new(deadbands: Deadbands, heartbeat: Option<Duration>) -> Self {
+        UpdateFilter {
+            deadbands,
+            heartbeat,
+            last_sent: None,
+        }
+    }
+
+    /// Returns true if the metrics selected by the field mask should be
+    /// sent and takes them as the last sent metrics in that case
+    pub fn should_send(
+        &mut self,
+        metrics: &Arc<Metrics>,
+        field_mask: &FieldMask,
+        now: Instant,
+    ) -> bool {
+        let send = match &self.last_sent {
+            Some((sent_at, last_metrics)) => {
+                matches!(self.heartbeat, Some(heartbeat) if now.duration_since(*sent_at) >= heartbeat)
+                    || self.deadbands.moved(last_metrics, metrics, field_mask)
+            }
+            None => true,
+        };
+
+        if send {
+            self.last_sent = Some((now, Arc::clone(metrics)));
+        }
+
+        send
+    }
+
+    /// The time until the heartbeat is due, none without a heartbeat
+    pub fn until_heartbeat(&self, now: Instant) -> Option<Duration> {
+        let heartbeat = self.heartbeat?;
+
+        match &self.last_sent {
+            Some((sent_at, _)) => Some((*sent_at + heartbeat).saturating_duration_since(now)),
+            None => Some(heartbeat),
+        }
+    }
+}
+
+/// Values that are absent aren't metrics
+trait MetricValue {
+    fn metric_value(&self) -> Option<f64>;
+}
+
+impl MetricValue for u64 {
+    fn metric_value(&self) -> Option<f64> {
+        Some(*self as f64)
+    }
+}
+
+impl MetricValue for f64 {
+    fn metric_value(&self) -> Option<f64> {
+        Some(*self)
+    }
+}
+
+impl MetricValue for bool {
+    fn metric_value(&self) -> Option<f64> {
+        Some(if *self { 1.0 } else { 0.0 })
+    }
+}
+
+impl<T: MetricValue> MetricValue for Option<T> {
+    fn metric_value(&self) -> Option<f64> {
+        self.as_ref().and_then(MetricValue::metric_value)
+    }
+}
+
+macro_rules! add_fields {
+    ($metrics:expr, $prefix:expr, $message:expr, $($field:ident),+) => {
+        $(
+            if let Some(value) = $message.$field.metric_value() {
+                $metrics.insert(format!("{}.{}", $prefix, stringify!($field)), value);
+            }
+        )+
+    };
+}
+
+/// Escapes the separators in a name segment of a path
+fn escape(name: &str) -> Cow<'_, str> {
+    if name.contains(&['.', '*', '\\'][..]) {
+        let mut escaped = String::with_capacity(name.len() + 2);
+        for c in name.chars() {
+            if matches!(c, '.' | '*' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+
+        Cow::Owned(escaped)
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Collects the numeric values of the message, the timestamps are left out
+pub fn flatten(node_stats: &proto::NodeStats) -> Metrics {
+    let mut metrics = Metrics::new();
+
+    if let Some(bandwidth) = &node_stats.used_bandwidth {
+        add_bandwidth(&mut metrics, "used_bandwidth", bandwidth);
+    }
+
+    for (name, bandwidth) in &node_stats.interface_bandwidth {
+        add_bandwidth(
+            &mut metrics,
+            &format!("interface_bandwidth.{}", escape(name)),
+            bandwidth,
+        );
+    }
+
+    if let Some(cpu) = &node_stats.cpu {
+        if let Some(total) = &cpu.total {
+            add_fields!(metrics, "cpu.total", total, user, system, iowait, steal);
+        }
+
+        for (core, usage) in cpu.cores.iter().enumerate() {
+            add_fields!(
+                metrics,
+                format!("cpu.cores.{}", core),
+                usage,
+                user,
+                system,
+                iowait,
+                steal
+            );
+        }
+    }
+
+    if let Some(memory) = &node_stats.memory {
+        add_fields!(
+            metrics, "memory", memory, total, available, cached, dirty, swap_total, swap_free
+        );
+    }
+
+    if let Some(load) = &node_stats.load {
+        if let Some(load_average) = &load.load_average {
+            add_fields!(
+                metrics,
+                "load.load_average",
+                load_average,
+                one,
+                five,
+                fifteen,
+                runnable_tasks,
+                total_tasks
+            );
+        }
+
+        for (name, pressure) in &[
+            ("cpu_pressure", &load.cpu_pressure),
+            ("memory_pressure", &load.memory_pressure),
+            ("io_pressure", &load.io_pressure),
+        ] {
+            if let Some(pressure) = pressure {
+                for (kind, values) in &[("some", &pressure.some), ("full", &pressure.full)] {
+                    if let Some(values) = values {
+                        add_fields!(
+                            metrics,
+                            format!("load.{}.{}", name, kind),
+                            values,
+                            avg10,
+                            avg60,
+                            avg300
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    for cgroup in &node_stats.cgroups {
+        let prefix = format!("cgroups.{}", escape(&cgroup.name));
+        add_fields!(
+            metrics,
+            prefix,
+            cgroup,
+            cpu_usage,
+            cpu_user,
+            cpu_system,
+            memory_current
+        );
+
+        if let Some(network) = &cgroup.network {
+            add_fields!(
+                metrics,
+                format!("{}.network", prefix),
+                network,
+                rx_bps,
+                tx_bps
+            );
+        }
+    }
+
+    if let Some(tcp_connections) = &node_stats.tcp_connections {
+        add_fields!(
+            metrics,
+            "tcp_connections",
+            tcp_connections,
+            established,
+            syn_sent,
+            syn_recv,
+            fin_wait1,
+            fin_wait2,
+            time_wait,
+            close,
+            close_wait,
+            last_ack,
+            listen,
+            closing
+        );
+    }
+
+    for filesystem in &node_stats.filesystems {
+        add_fields!(
+            metrics,
+            format!("filesystems.{}", escape(&filesystem.path)),
+            filesystem,
+            total_bytes,
+            free_bytes,
+            available_bytes,
+            total_inodes,
+            free_inodes,
+            available_inodes
+        );
+    }
+
+    for disk in &node_stats.disks {
+        add_fields!(
+            metrics,
+            format!("disks.{}", escape(&disk.name)),
+            disk,
+            read_bytes_per_second,
+            write_bytes_per_second,
+            read_iops,
+            write_iops,
+            utilization
+        );
+    }
+
+    if let Some(thermal) = &node_stats.thermal {
+        add_fields!(
+            metrics,
+            "thermal",
+            thermal,
+            core_throttle_count,
+            package_throttle_count,
+            warning,
+            critical
+        );
+
+        for temperature in &thermal.temperatures {
+            add_fields!(
+                metrics,
+                format!("thermal.temperatures.{}", escape(&temperature.label)),
+                temperature,
+                celsius,
+                warning,
+                critical
+            );
+        }
+    }
+
+    for (name, value) in &node_stats.custom {
+        metrics.insert(format!("custom.{}", escape(name)), *value);
+    }
+
+    if let Some(nginx) = &node_stats.nginx {
+        add_fields!(
+            metrics,
+            "nginx",
+            nginx,
+            active_connections,
+            reading,
+            writing,
+            waiting,
+            accepts_per_second,
+            handled_per_second,
+            requests_per_second
+        );
+    }
+
+    metrics
+}
+
+fn add_bandwidth(metrics: &mut Metrics, prefix: &str, bandwidth: &proto::Bandwidth) {
+    add_fields!(
+        metrics,
+        prefix,
+        bandwidth,
+        tx_bps,
+        rx_bps,
+        capacity_bps,
+        tx_utilization,
+        rx_utilization,
+        counter_reset,
+        counter_resets,
+        tx_ewma_bps,
+        rx_ewma_bps,
+        tx_window_mean_bps,
+        rx_window_mean_bps,
+        tx_window_max_bps,
+        rx_window_max_bps
+    );
+
+    if let Some(percentiles) = &bandwidth.percentiles {
+        for (window, window_percentiles) in &[
+            ("one", &percentiles.one),
+            ("five", &percentiles.five),
+            ("fifteen", &percentiles.fifteen),
+        ] {
+            if let Some(window_percentiles) = window_percentiles {
+                for (direction, rate_percentiles) in &[
+                    ("tx", &window_percentiles.tx),
+                    ("rx", &window_percentiles.rx),
+                ] {
+                    if let Some(rate_percentiles) = rate_percentiles {
+                        add_fields!(
+                            metrics,
+                            format!("{}.percentiles.{}.{}", prefix, window, direction),
+                            rate_percentiles,
+                            p50_bps,
+                            p95_bps,
+                            p99_bps,
+                            peak_bps
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(packet_rates) = &bandwidth.packet_rates {
+        add_fields!(
+            metrics,
+            format!("{}.packet_rates", prefix),
+            packet_rates,
+            rx_packets,
+            tx_packets,
+            rx_errors,
+            tx_errors,
+            rx_dropped,
+            tx_dropped,
+            rx_fifo_errors,
+            tx_fifo_errors,
+            collisions
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadband(metric: &str, absolute: f64, relative: f64) -> proto::Deadband {
+        proto::Deadband {
+            metric: metric.into(),
+            absolute,
+            relative,
+        }
+    }
+
+    fn metrics(values: &[(&str, f64)]) -> Metrics {
+        values
+            .iter()
+            .map(|(path, value)| (path.to_string(), *value))
+            .collect()
+    }
+
+    /// Hands out distinct values and counts the fields they were set to
+    #[derive(Default)]
+    struct Fields {
+        count: usize,
+    }
+
+    impl Fields {
+        fn u64(&mut self) -> u64 {
+            self.count += 1;
+            self.count as u64
+        }
+
+        fn f64(&mut self) -> f64 {
+            self.u64() as f64
+        }
+
+        fn bool(&mut self) -> bool {
+            self.count += 1;
+            true
+        }
+    }
+
+    // the messages are built without defaults, so that a new proto field
+    // doesn't compile until it's set here and counted in test_flatten_all_fields
+
+    fn bandwidth(fields: &mut Fields) -> proto::Bandwidth {
+        proto::Bandwidth {
+            tx_bps: fields.u64(),
+            rx_bps: fields.u64(),
+            capacity_bps: Some(fields.u64()),
+            tx_utilization: Some(fields.f64()),
+            rx_utilization: Some(fields.f64()),
+            counter_reset: fields.bool(),
+            counter_resets: fields.u64(),
+            tx_ewma_bps: fields.u64(),
+            rx_ewma_bps: fields.u64(),
+            tx_window_mean_bps: fields.u64(),
+            rx_window_mean_bps: fields.u64(),
+            tx_window_max_bps: fields.u64(),
+            rx_window_max_bps: fields.u64(),
+            percentiles: Some(proto::BandwidthPercentiles {
+                one: Some(window_percentiles(fields)),
+                five: Some(window_percentiles(fields)),
+                fifteen: Some(window_percentiles(fields)),
+            }),
+            packet_rates: Some(proto::PacketRates {
+                rx_packets: fields.f64(),
+                tx_packets: fields.f64(),
+                rx_errors: fields.f64(),
+                tx_errors: fields.f64(),
+                rx_dropped: fields.f64(),
+                tx_dropped: fields.f64(),
+                rx_fifo_errors: fields.f64(),
+                tx_fifo_errors: fields.f64(),
+                collisions: fields.f64(),
+            }),
+        }
+    }
+
+    fn window_percentiles(fields: &mut Fields) -> proto::WindowPercentiles {
+        let mut rate_percentiles = || proto::RatePercentiles {
+            p50_bps: fields.u64(),
+            p95_bps: fields.u64(),
+            p99_bps: fields.u64(),
+            peak_bps: fields.u64(),
+        };
+
+        proto::WindowPercentiles {
+            tx: Some(rate_percentiles()),
+            rx: Some(rate_percentiles()),
+        }
+    }
+
+    fn cpu_usage(fields: &mut Fields) -> proto::CpuUsage {
+        proto::CpuUsage {
+            user: fields.f64(),
+            system: fields.f64(),
+            iowait: fields.f64(),
+            steal: fields.f64(),
+        }
+    }
+
+    fn pressure(fields: &mut Fields) -> proto::Pressure {
+        let mut values = || proto::PressureValues {
+            avg10: fields.f64(),
+            avg60: fields.f64(),
+            avg300: fields.f64(),
+        };
+
+        proto::Pressure {
+            some: Some(values()),
+            full: Some(values()),
+        }
+    }
+
+    fn all_fields(fields: &mut Fields) -> proto::NodeStats {
+        proto::NodeStats {
+            used_bandwidth: Some(bandwidth(fields)),
+            cpu: Some(proto::CpuStats {
+                total: Some(cpu_usage(fields)),
+                cores: vec![cpu_usage(fields)],
+            }),
+            memory: Some(proto::MemoryStats {
+                total: fields.u64(),
+                available: fields.u64(),
+                cached: fields.u64(),
+                dirty: fields.u64(),
+                swap_total: fields.u64(),
+                swap_free: fields.u64(),
+            }),
+            load: Some(proto::LoadStats {
+                load_average: Some(proto::LoadAverage {
+                    one: fields.f64(),
+                    five: fields.f64(),
+                    fifteen: fields.f64(),
+                    runnable_tasks: fields.u64(),
+                    total_tasks: fields.u64(),
+                }),
+                cpu_pressure: Some(pressure(fields)),
+                memory_pressure: Some(pressure(fields)),
+                io_pressure: Some(pressure(fields)),
+            }),
+            interface_bandwidth: vec![("eth0".to_string(), bandwidth(fields))]
+                .into_iter()
+                .collect(),
+            cgroups: vec![proto::CgroupStats {
+                name: "system.slice".into(),
+                cpu_usage: fields.f64(),
+                cpu_user: fields.f64(),
+                cpu_system: fields.f64(),
+                memory_current: fields.u64(),
+                network: Some(proto::CgroupNetwork {
+                    rx_bps: fields.u64(),
+                    tx_bps: fields.u64(),
+                }),
+            }],
+            tcp_connections: Some(proto::TcpConnectionStats {
+                established: fields.u64(),
+                syn_sent: fields.u64(),
+                syn_recv: fields.u64(),
+                fin_wait1: fields.u64(),
+                fin_wait2: fields.u64(),
+                time_wait: fields.u64(),
+                close: fields.u64(),
+                close_wait: fields.u64(),
+                last_ack: fields.u64(),
+                listen: fields.u64(),
+                closing: fields.u64(),
+            }),
+            filesystems: vec![proto::FilesystemStats {
+                path: "/var/lib/docker".into(),
+                total_bytes: fields.u64(),
+                free_bytes: fields.u64(),
+                available_bytes: fields.u64(),
+                total_inodes: fields.u64(),
+                free_inodes: fields.u64(),
+                available_inodes: fields.u64(),
+            }],
+            disks: vec![proto::DiskStats {
+                name: "sda".into(),
+                read_bytes_per_second: fields.u64(),
+                write_bytes_per_second: fields.u64(),
+                read_iops: fields.f64(),
+                write_iops: fields.f64(),
+                utilization: fields.f64(),
+            }],
+            thermal: Some(proto::ThermalStats {
+                temperatures: vec![proto::Temperature {
+                    label: "Package id 0".into(),
+                    celsius: fields.f64(),
+                    warning: fields.bool(),
+                    critical: fields.bool(),
+                }],
+                core_throttle_count: fields.u64(),
+                package_throttle_count: fields.u64(),
+                warning: fields.bool(),
+                critical: fields.bool(),
+            }),
+            custom: vec![("cache.hit_ratio".to_string(), fields.f64())]
+                .into_iter()
+                .collect(),
+            nginx: Some(proto::NginxStats {
+                active_connections: fields.u64(),
+                reading: fields.u64(),
+                writing: fields.u64(),
+                waiting: fields.u64(),
+                accepts_per_second: fields.f64(),
+                handled_per_second: fields.f64(),
+                requests_per_second: fields.f64(),
+            }),
+            timestamp_millis: 42,
+            source_timestamp_millis: vec![("CpuStatsProvider".to_string(), 42)]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_flatten_all_fields() {
+        let mut fields = Fields::default();
+        let node_stats = all_fields(&mut fields);
+
+        assert_eq!(fields.count, flatten(&node_stats).len());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let node_stats = proto::NodeStats {
+            memory: Some(proto::MemoryStats {
+                available: 1024,
+                ..Default::default()
+            }),
+            disks: vec![proto::DiskStats {
+                name: "sda".into(),
+                utilization: 0.5,
+                ..Default::default()
+            }],
+            thermal: Some(proto::ThermalStats {
+                warning: true,
+                ..Default::default()
+            }),
+            custom: vec![(r"cache.hit*\ratio".to_string(), 0.75)]
+                .into_iter()
+                .collect(),
+            timestamp_millis: 42,
+            ..Default::default()
+        };
+
+        let metrics = flatten(&node_stats);
+
+        assert_eq!(Some(&1024.0), metrics.get("memory.available"));
+        assert_eq!(Some(&0.5), metrics.get("disks.sda.utilization"));
+        assert_eq!(Some(&1.0), metrics.get("thermal.warning"));
+        assert_eq!(Some(&0.75), metrics.get(r"custom.cache\.hit\*\\ratio"));
+        assert_eq!(6 + 5 + 4 + 1, metrics.len());
+    }
+
+    #[test]
+    fn test_moved() {
+        let deadbands = Deadbands::new(vec![
+            deadband("load", 0.5, 0.0),
+            deadband("disks.*.utilization", 0.0, 0.1),
+            deadband("disks.sdb.utilization", 0.0, 0.0),
+            deadband("cgroups.system", 100.0, 0.0),
+        ])
+        .unwrap();
+
+        let last = metrics(&[
+            ("load.load_average.one", 1.0),
+            ("disks.sda.utilization", 0.5),
+            ("disks.sdb.utilization", 0.5),
+            ("memory.available", 1024.0),
+            ("cgroups.system.cpu_usage", 0.5),
+            (r"cgroups.system\.slice.cpu_usage", 0.5),
+        ]);
+        let moved = |path: &str, value: f64| {
+            let mut current = last.clone();
+            current.insert(path.into(), value);
+
+            deadbands.moved(&last, &current, &FieldMask::default())
+        };
+
+        assert!(!deadbands.moved(&last, &last, &FieldMask::default()));
+        assert!(!moved("load.load_average.one", 1.5));
+        assert!(moved("load.load_average.one", 1.6));
+        assert!(!moved("disks.sda.utilization", 0.54));
+        assert!(moved("disks.sda.utilization", 0.56));
+        assert!(moved("disks.sdb.utilization", 0.51));
+        assert!(moved("memory.available", 1023.0));
+        assert!(moved("custom.requests", 0.0));
+        // the deadband of the system cgroup doesn't apply to system.slice
+        assert!(!moved("cgroups.system.cpu_usage", 1.5));
+        assert!(moved(r"cgroups.system\.slice.cpu_usage", 1.5));
+    }
+
+    #[test]
+    fn test_moved_outside_of_field_mask() {
+        let deadbands = Deadbands::default();
+        let field_mask = FieldMask::new(vec!["memory".into()]).unwrap();
+        let last = metrics(&[("memory.available", 1024.0), ("load.load_average.one", 1.0)]);
+
+        let current = metrics(&[("memory.available", 1024.0), ("load.load_average.one", 2.0)]);
+        assert!(!deadbands.moved(&last, &current, &field_mask));
+
+        let current = metrics(&[("memory.available", 1024.0)]);
+        assert!(!deadbands.moved(&last, &current, &field_mask));
+        assert!(deadbands.moved(&last, &current, &FieldMask::default()));
+    }
+
+    #[test]
+    fn test_invalid_deadbands() {
+        assert!(Deadbands::new(vec![deadband("gpu", 1.0, 0.0)]).is_err());
+        assert!(Deadbands::new(vec![deadband("cpu", -1.0, 0.0)]).is_err());
+        assert!(Deadbands::new(vec![deadband("cpu", 0.0, f64::NAN)]).is_err());
+        assert!(Deadbands::new(vec![deadband(r"cgroups.a\", 0.1, 0.0)]).is_err());
+        assert!(Deadbands::new(vec![deadband("*.utilization", 0.1, 0.0)]).is_ok());
+    }
+
+    #[test]
+    fn test_update_filter() {
+        let deadbands = Deadbands::new(vec![deadband("memory", 100.0, 0.0)]).unwrap();
+        let mut filter = UpdateFilter::new(deadbands, Some(Duration::from_secs(10)));
+        let field_mask = FieldMask::default();
+        let metrics = |available| Arc::new(metrics(&[("memory.available", available)]));
+        let start = Instant::now();
+
+        assert_eq!(Some(Duration::from_secs(10)), filter.until_heartbeat(start));
+        assert!(filter.should_send(&metrics(1000.0), &field_mask, start));
+        assert!(!filter.should_send(&metrics(1050.0), &field_mask, start));
+        // compared with the last sent stats
+        assert!(filter.should_send(
+            &metrics(1101.0),
+            &field_mask,
+            start + Duration::from_secs(1)
+        ));
+
+        let later = start + Duration::from_secs(6);
+        assert_eq!(Some(Duration::from_secs(5)), filter.until_heartbeat(later));
+        assert!(!filter.should_send(&metrics(1101.0), &field_mask, later));
+        assert!(filter.should_send(
+            &metrics(1101.0),
+            &field_mask,
+            start + Duration::from_secs(11)
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+
+use super::proto;
+
+/// Names of the stat groups of the NodeStats message that can be selected
+pub(super) const FIELDS: &[&str] = &[
+    "used_bandwidth",
+    "cpu",
+    "memory",
+    "load",
+    "interface_bandwidth",
+    "cgroups",
+    "tcp_connections",
+    "filesystems",
+    "disks",
+    "thermal",
+    "custom",
+    "nginx",
+];
+
+/// Selects the stat groups of the NodeStats message sent to a client by
+/// their field names, an empty selection includes all of them
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FieldMask {
+    fields: HashSet<String>,
+}
+
+impl FieldMask {
+    pub fn new(fields: Vec<String>) -> anyhow::Result<Self> {
+        if let Some(field) = fields.iter().find(|f| !FIELDS.contains(&f.as_str())) {
+            return Err(anyhow!(
+                "Unknown node stats field {}, expected one of {}",
+                field,
+                FIELDS.join(", ")
+            ));
+        }
+
+        Ok(FieldMask {
+            fields: fields.into_iter().collect(),
+        })
+    }
+
+    pub fn includes_all(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    fn includes(&self, field: &str) -> bool {
+        self.includes_all() || self.fields.contains(field)
+    }
+
+    /// Whether the flattened metric belongs to one of the selected fields
+    pub fn includes_metric(&self, path: &str) -> bool {
+        self.includes(path.split('.').next().unwrap_or_default())
+    }
+
+    fn pick<T: Default + Clone>(&self, field: &str, value: &T) -> T {
+        if self.includes(field) {
+            value.clone()
+        } else {
+            Default::default()
+        }
+    }
+
+    /// Copies the selected fields, the timestamps are always kept
+    pub fn apply(&self, node_stats: &proto::NodeStats) -> proto::NodeStats {
+        proto::NodeStats {
+            used_bandwidth: self.pick("used_bandwidth", &node_stats.used_bandwidth),
+            cpu: self.pick("cpu", &node_stats.cpu),
+            memory: self.pick("memory", &node_stats.memory),
+            load: self.pick("load", &node_stats.load),
+            interface_bandwidth: self.pick("interface_bandwidth", &node_stats.interface_bandwidth),
+            cgroups: self.pick("cgroups", &node_stats.cgroups),
+            tcp_connections: self.pick("tcp_connections", &node_stats.tcp_connections),
+            filesystems: self.pick("filesystems", &node_stats.filesystems),
+            disks: self.pick("disks", &node_stats.disks),
+            thermal: self.pick("thermal", &node_stats.thermal),
+            custom: self.pick("custom", &node_stats.custom),
+            nginx: self.pick("nginx", &node_stats.nginx),
+            timestamp_millis: node_stats.timestamp_millis,
+            source_timestamp_millis: node_stats.source_timestamp_millis.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_stats() -> proto::NodeStats {
+        proto::NodeStats {
+            used_bandwidth: Some(Default::default()),
+            cpu: Some(Default::default()),
+            memory: Some(Default::default()),
+            timestamp_millis: 42,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let field_mask = FieldMask::new(vec!["used_bandwidth".into(), "memory".into()]).unwrap();
+
+        assert_eq!(
+            proto::NodeStats {
+                used_bandwidth: Some(Default::default()),
+                memory: Some(Default::default()),
+                timestamp_millis: 42,
+                ..Default::default()
+            },
+            field_mask.apply(&node_stats())
+        );
+        assert_eq!(node_stats(), FieldMask::default().apply(&node_stats()));
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(FieldMask::new(vec!["cpu".into(), "gpu".into()]).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use prost::bytes::{BufMut, Bytes};
+use prost::Message;
+use tonic::body::BoxBody;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder, ProstCodec};
+use tonic::codegen::{http, BoxFuture, Context, HttpBody, Never, Poll, Service, StdError};
+use tonic::server::{Grpc, ServerStreamingService, UnaryService};
+use tonic::transport::NamedService;
+use tonic::{Code, Request, Response, Status};
+
+use super::{proto, LiveStatsStream, NodeStatsService};
+
+const GET_LIVE_STATS_PATH: &str = "/nodestats.NodeStatsService/GetLiveStats";
+const GET_STATS_PATH: &str = "/nodestats.NodeStatsService/GetStats";
+
+/// Serves the node stats service. Unlike the generated server it sends the
+/// live stats pre-encoded, so every snapshot is encoded once per field mask
+/// instead of once per stream.
+#[derive(Clone)]
+pub struct NodeStatsServiceServer {
+    service: Arc<NodeStatsService>,
+}
+
+impl NodeStatsServiceServer {
+    pub fn new(service: NodeStatsService) -> Self {
+        Self {
+            service: Arc::new(service),
+        }
+    }
+}
+
+impl NamedService for NodeStatsServiceServer {
+    const NAME: &'static str = "nodestats.NodeStatsService";
+}
+
+impl<B> Service<http::Request<B>> for NodeStatsServiceServer
+where
+    B: HttpBody + Send + Sync + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let service = Arc::clone(&self.service);
+
+        match req.uri().path() {
+            GET_LIVE_STATS_PATH => Box::pin(async move {
+                let mut grpc = Grpc::new(EncodedCodec);
+                Ok(grpc.server_streaming(GetLiveStats(service), req).await)
+            }),
+            GET_STATS_PATH => Box::pin(async move {
+                let mut grpc = Grpc::new(ProstCodec::default());
+                Ok(grpc.unary(GetStats(service), req).await)
+            }),
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", (Code::Unimplemented as i32).to_string())
+                    .header("content-type", "application/grpc")
+                    .body(BoxBody::empty())
+                    .unwrap())
+            }),
+        }
+    }
+}
+
+struct GetLiveStats(Arc<NodeStatsService>);
+
+impl ServerStreamingService<proto::LiveNodeStatsRequest> for GetLiveStats {
+    type Response = Bytes;
+    type ResponseStream = LiveStatsStream;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<proto::LiveNodeStatsRequest>) -> Self::Future {
+        let service = Arc::clone(&self.0);
+        Box::pin(async move { service.get_live_stats(request).await })
+    }
+}
+
+struct GetStats(Arc<NodeStatsService>);
+
+impl UnaryService<proto::NodeStatsRequest> for GetStats {
+    type Response = proto::NodeStats;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<proto::NodeStatsRequest>) -> Self::Future {
+        let service = Arc::clone(&self.0);
+        Box::pin(async move { service.get_stats(request).await })
+    }
+}
+
+/// Decodes the live stats requests and writes the already encoded live stats
+/// messages as they are
+#[derive(Debug, Default)]
+struct EncodedCodec;
+
+impl Codec for EncodedCodec {
+    type Encode = Bytes;
+    type Decode = proto::LiveNodeStatsRequest;
+    type Encoder = EncodedCodec;
+    type Decoder = EncodedCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        EncodedCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        EncodedCodec
+    }
+}
+
+impl Encoder for EncodedCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for EncodedCodec {
+    type Item = proto::LiveNodeStatsRequest;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Status> {
+        proto::LiveNodeStatsRequest::decode(src)
+            .map(Some)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tonic::transport::{Channel, Server};
+
+    use super::super::LiveStatsIntervals;
+    use crate::stats;
+
+    #[tokio::test]
+    async fn test_serve_live_stats() {
+        let service = NodeStatsService::new(
+            Arc::new(stats::NodeStatsProvider::new(vec![])),
+            LiveStatsIntervals {
+                default: Duration::from_secs(1),
+                min: Duration::from_millis(10),
+                max: Duration::from_secs(60),
+                max_heartbeat: Duration::from_secs(600),
+            },
+        );
+        let addr = "127.0.0.1:50151".parse().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(NodeStatsServiceServer::new(service))
+                .serve(addr),
+        );
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        let channel = Channel::from_static("http://127.0.0.1:50151")
+            .connect()
+            .await
+            .unwrap();
+        let mut client = proto::node_stats_service_client::NodeStatsServiceClient::new(channel);
+
+        let mut stream = client
+            .get_live_stats(proto::LiveNodeStatsRequest {
+                interval_millis: 10,
+                fields: vec!["cpu".into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        for _ in 0..2 {
+            let node_stats = stream.message().await.unwrap().unwrap();
+            assert_eq!(None, node_stats.used_bandwidth);
+        }
+
+        let node_stats = client
+            .get_stats(proto::NodeStatsRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(node_stats.used_bandwidth.is_some());
+    }
+}
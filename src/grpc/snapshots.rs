@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Duration;
+
+use log::info;
+use prost::bytes::{Bytes, BytesMut};
+use prost::Message;
+use tokio::sync::broadcast::{self, RecvError, TryRecvError};
+use tokio::time;
+
+use super::deadband::{flatten, Metrics};
+use super::field_mask::FieldMask;
+use super::proto;
+use crate::stats;
+
+/// Number of snapshots kept for streams that didn't receive them yet
+const SNAPSHOT_CAPACITY: usize = 16;
+
+pub type Snapshot = Arc<NodeStatsSnapshot>;
+
+/// A node stats update converted once for all live stats streams
+#[derive(Debug)]
+pub struct NodeStatsSnapshot {
+    pub message: proto::NodeStats,
+    metrics: Mutex<Option<Arc<Metrics>>>,
+    /// The message encoded for every field mask requested so far
+    encoded: Mutex<Vec<(FieldMask, Bytes)>>,
+}
+
+impl NodeStatsSnapshot {
+    /// The numeric values of the message compared by the update filters,
+    /// flattened by the first stream that filters this snapshot
+    pub fn metrics(&self) -> Arc<Metrics> {
+        let mut metrics = self.metrics.lock().unwrap();
+
+        Arc::clone(metrics.get_or_insert_with(|| Arc::new(flatten(&self.message))))
+    }
+
+    /// The masked message encoded once for all streams with the same mask
+    pub fn encoded(&self, field_mask: &FieldMask) -> Bytes {
+        let mut encoded = self.encoded.lock().unwrap();
+
+        if let Some((_, bytes)) = encoded.iter().find(|(mask, _)| mask == field_mask) {
+            return bytes.clone();
+        }
+
+        let bytes = if field_mask.includes_all() {
+            encode(&self.message)
+        } else {
+            encode(&field_mask.apply(&self.message))
+        };
+        encoded.push((field_mask.clone(), bytes.clone()));
+
+        bytes
+    }
+}
+
+fn encode(message: &proto::NodeStats) -> Bytes {
+    let mut buf = BytesMut::with_capacity(message.encoded_len());
+    message
+        .encode(&mut buf)
+        .expect("The buffer has the encoded length");
+
+    buf.freeze()
+}
+
+impl From<&stats::NodeStats> for NodeStatsSnapshot {
+    fn from(node_stats: &stats::NodeStats) -> Self {
+        NodeStatsSnapshot {
+            message: proto::NodeStats::from(node_stats),
+            metrics: Mutex::new(None),
+            encoded: Mutex::new(vec![]),
+        }
+    }
+}
+
+/// Converts every node stats update once and shares the snapshot with all
+/// live stats streams instead of converting and flattening it per stream
+pub struct SnapshotBroadcaster {
+    latest: RwLock<Snapshot>,
+    /// None once the node stats aren't updated anymore
+    sender: Mutex<Option<broadcast::Sender<Snapshot>>>,
+    /// One ticker per requested interval sends the latest snapshot to all
+    /// interval streams of that interval
+    tickers: Mutex<HashMap<Duration, broadcast::Sender<Snapshot>>>,
+}
+
+impl SnapshotBroadcaster {
+    pub fn new(node_stats: &stats::NodeStats) -> Self {
+        let (tx, _) = broadcast::channel(SNAPSHOT_CAPACITY);
+
+        Self {
+            latest: RwLock::new(Arc::new(NodeStatsSnapshot::from(node_stats))),
+            sender: Mutex::new(Some(tx)),
+            tickers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Publishes the updates of the provider until it stops updating
+    pub fn start(node_stats_provider: &stats::NodeStatsProvider) -> Arc<Self> {
+        let broadcaster = Arc::new(Self::new(&node_stats_provider.current_node_stats()));
+
+        start_publish_loop(
+            Arc::downgrade(&broadcaster),
+            node_stats_provider.subscribe(),
+        );
+
+        broadcaster
+    }
+
+    /// Converts the node stats and sends them to the current subscribers,
+    /// never waits for lagging subscribers
+    pub fn publish(&self, node_stats: &stats::NodeStats) {
+        let snapshot = Arc::new(NodeStatsSnapshot::from(node_stats));
+        *self.latest.write().unwrap() = Arc::clone(&snapshot);
+
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            // fails without subscribers
+            let _ = sender.send(snapshot);
+        }
+    }
+
+    pub fn latest(&self) -> Snapshot {
+        Arc::clone(&self.latest.read().unwrap())
+    }
+
+    /// Receives the snapshots published after subscribing, none once the
+    /// node stats aren't updated anymore
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<Snapshot>> {
+        self.sender
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|sender| sender.subscribe())
+    }
+
+    /// Receives the latest snapshot every interval, even once the node stats
+    /// aren't updated anymore. Starts a ticker for the interval if no other
+    /// stream uses it yet.
+    pub fn subscribe_interval(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> broadcast::Receiver<Snapshot> {
+        let mut tickers = self.tickers.lock().unwrap();
+        if let Some(ticker) = tickers.get(&interval) {
+            return ticker.subscribe();
+        }
+
+        let (ticker, ticks) = broadcast::channel(SNAPSHOT_CAPACITY);
+        tickers.insert(interval, ticker);
+        start_ticker(Arc::downgrade(self), interval);
+
+        ticks
+    }
+
+    /// Sends the latest snapshot to the streams of the interval, false once
+    /// they are all gone
+    fn tick(&self, interval: Duration) -> bool {
+        let mut tickers = self.tickers.lock().unwrap();
+
+        match tickers.get(&interval) {
+            Some(ticker) if ticker.receiver_count() > 0 => {
+                let _ = ticker.send(self.latest());
+                true
+            }
+            Some(_) => {
+                tickers.remove(&interval);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
+
+fn start_ticker(broadcaster: Weak<SnapshotBroadcaster>, interval: Duration) {
+    info!(
+        "Start SnapshotBroadcaster ticker with interval {:?}",
+        interval
+    );
+
+    tokio::spawn(async move {
+        // the streams send the latest snapshot right after subscribing
+        let mut ticks = time::interval_at(time::Instant::now() + interval, interval);
+
+        loop {
+            ticks.tick().await;
+
+            let ticked = broadcaster
+                .upgrade()
+                .map_or(false, |broadcaster| broadcaster.tick(interval));
+            if !ticked {
+                info!(
+                    "No streams left with interval {:?}, ending ticker",
+                    interval
+                );
+                return;
+            }
+        }
+    });
+}
+
+fn start_publish_loop(
+    broadcaster: Weak<SnapshotBroadcaster>,
+    mut subscription: tokio::sync::watch::Receiver<Arc<stats::NodeStats>>,
+) {
+    info!("Start SnapshotBroadcaster publish loop");
+
+    tokio::spawn(async move {
+        while let Some(node_stats) = subscription.recv().await {
+            match broadcaster.upgrade() {
+                Some(broadcaster) => broadcaster.publish(&node_stats),
+                None => {
+                    info!(
+                        "Couldn't get a reference to the snapshot broadcaster, ending publish loop"
+                    );
+                    return;
+                }
+            }
+        }
+
+        info!("Node stats subscription closed, ending publish loop");
+        if let Some(broadcaster) = broadcaster.upgrade() {
+            broadcaster.close();
+        }
+    });
+}
+
+/// Receives the latest snapshot, a lagging subscriber skips the older ones.
+/// Returns none once the broadcaster is closed.
+pub async fn recv_latest(updates: &mut broadcast::Receiver<Snapshot>) -> Option<Snapshot> {
+    let mut latest = None;
+
+    // the channel reports being closed only once, afterwards it stays empty
+    loop {
+        match updates.try_recv() {
+            Ok(snapshot) => latest = Some(snapshot),
+            Err(TryRecvError::Lagged(skipped)) => {
+                info!("Skipped {} lagging node stats snapshots", skipped)
+            }
+            Err(TryRecvError::Closed) => return None,
+            Err(TryRecvError::Empty) if latest.is_some() => return latest,
+            Err(TryRecvError::Empty) => match updates.recv().await {
+                Ok(snapshot) => latest = Some(snapshot),
+                Err(RecvError::Lagged(skipped)) => {
+                    info!("Skipped {} lagging node stats snapshots", skipped)
+                }
+                Err(RecvError::Closed) => return None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_stats(cpu_user: f64) -> stats::NodeStats {
+        stats::NodeStats {
+            cpu: Some(Arc::new(stats::cpu::CpuStats {
+                total: stats::cpu::CpuUsage {
+                    user: cpu_user,
+                    ..Default::default()
+                },
+                cores: vec![],
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn cpu_user(snapshot: &Snapshot) -> f64 {
+        snapshot
+            .message
+            .cpu
+            .as_ref()
+            .unwrap()
+            .total
+            .as_ref()
+            .unwrap()
+            .user
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let broadcaster = SnapshotBroadcaster::new(&stats::NodeStats::default());
+        assert!(broadcaster.latest().message.cpu.is_none());
+
+        let mut updates = broadcaster.subscribe().unwrap();
+        broadcaster.publish(&node_stats(0.5));
+
+        let snapshot = recv_latest(&mut updates).await.unwrap();
+        assert_eq!(0.5, cpu_user(&snapshot));
+        assert!(Arc::ptr_eq(&snapshot, &broadcaster.latest()));
+        assert_eq!(Some(&0.5), snapshot.metrics().get("cpu.total.user"));
+
+        broadcaster.close();
+        assert!(broadcaster.subscribe().is_none());
+        assert!(recv_latest(&mut updates).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_skips_ahead() {
+        let broadcaster = SnapshotBroadcaster::new(&stats::NodeStats::default());
+        let mut updates = broadcaster.subscribe().unwrap();
+
+        for i in 0..SNAPSHOT_CAPACITY * 2 {
+            broadcaster.publish(&node_stats(i as f64));
+        }
+
+        let snapshot = recv_latest(&mut updates).await.unwrap();
+        assert_eq!((SNAPSHOT_CAPACITY * 2 - 1) as f64, cpu_user(&snapshot));
+    }
+
+    #[test]
+    fn test_encoded_once_per_field_mask() {
+        let snapshot = NodeStatsSnapshot::from(&node_stats(0.5));
+        let cpu = FieldMask::new(vec!["cpu".into()]).unwrap();
+
+        let encoded = snapshot.encoded(&FieldMask::default());
+        assert_eq!(
+            snapshot.message,
+            proto::NodeStats::decode(encoded.clone()).unwrap()
+        );
+        assert_eq!(encoded, snapshot.encoded(&FieldMask::default()));
+
+        let masked = snapshot.encoded(&cpu);
+        let message = proto::NodeStats::decode(masked.clone()).unwrap();
+        assert!(message.cpu.is_some());
+        assert!(message.used_bandwidth.is_none());
+        assert_eq!(masked.as_ptr(), snapshot.encoded(&cpu).as_ptr());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_interval() {
+        let broadcaster = Arc::new(SnapshotBroadcaster::new(&node_stats(0.5)));
+        let interval = Duration::from_millis(10);
+
+        // streams with the same interval share a ticker
+        let mut first = broadcaster.subscribe_interval(interval);
+        let mut second = broadcaster.subscribe_interval(interval);
+        assert_eq!(1, broadcaster.tickers.lock().unwrap().len());
+
+        let snapshot = recv_latest(&mut first).await.unwrap();
+        assert!(Arc::ptr_eq(&snapshot, &broadcaster.latest()));
+        assert!(Arc::ptr_eq(
+            &snapshot,
+            &recv_latest(&mut second).await.unwrap()
+        ));
+
+        drop(first);
+        drop(second);
+        time::delay_for(interval * 3).await;
+        assert!(broadcaster.tickers.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_start() {
+        let provider = stats::NodeStatsProvider::new(vec![]);
+        let broadcaster = SnapshotBroadcaster::start(&provider);
+        let mut updates = broadcaster.subscribe();
+
+        // without data sources the node stats are never updated
+        if let Some(updates) = updates.as_mut() {
+            while recv_latest(updates).await.is_some() {}
+        }
+        assert!(broadcaster.subscribe().is_none());
+    }
+}
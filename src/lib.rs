@@ -2,3 +2,6 @@ pub mod grpc;
 pub mod settings;
 pub mod stats;
 pub mod util;
+
+#[cfg(test)]
+mod test_util;
@@ -4,9 +4,23 @@ use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
 
 use node_stats_service::{
     grpc,
-    settings::Settings,
-    stats::bandwidth::{CounterRateBandwidthProvider, FileCounterSource},
-    stats::NodeStatsProvider,
+    settings::{CounterSourceKind, Settings},
+    stats::bandwidth::{
+        CounterRateBandwidthProvider, CountersSnapshot, FileCounterSource, InterfaceDiscovery,
+        LinkOptions, MultiInterfaceBandwidthProvider, NetlinkLinkStats, ProcNetDev,
+        SnapshotCounterSource,
+    },
+    stats::cgroup::{CgroupSelection, CgroupStatsProvider},
+    stats::cpu::CpuStatsProvider,
+    stats::custom::CustomMetricsProvider,
+    stats::disk::DiskStatsProvider,
+    stats::filesystem::FilesystemStatsProvider,
+    stats::load::LoadStatsProvider,
+    stats::memory::MemoryStatsProvider,
+    stats::nginx::NginxStatsProvider,
+    stats::tcp_connections::TcpConnectionStatsProvider,
+    stats::thermal::{ThermalStatsProvider, ThermalThresholds},
+    stats::{NodeStatsDataSource, NodeStatsProvider},
 };
 
 #[tokio::main]
@@ -14,17 +28,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     let settings = Settings::from_file("config.yml").expect("Failed to load config");
 
-    let node_stats_service = grpc::NodeStatsService {
-        node_stats_provider: Arc::new(NodeStatsProvider::new(vec![Box::new(
-            CounterRateBandwidthProvider::new(
-                FileCounterSource::new(
-                    &settings.node_stats.bandwidth.rx_file,
-                    &settings.node_stats.bandwidth.tx_file,
-                ),
-                settings.node_stats.bandwidth.update_interval,
-            ),
-        )])),
-    };
+    let node_stats_service = grpc::NodeStatsService::new(
+        Arc::new(NodeStatsProvider::new(build_data_sources(&settings))),
+        grpc::LiveStatsIntervals {
+            default: settings.http.live_stats.default_interval,
+            min: settings.http.live_stats.min_interval,
+            max: settings.http.live_stats.max_interval,
+            max_heartbeat: settings.http.live_stats.max_heartbeat,
+        },
+    );
 
     let svc = grpc::NodeStatsServiceServer::new(node_stats_service);
 
@@ -37,6 +49,176 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn build_data_sources(settings: &Settings) -> Vec<Box<dyn NodeStatsDataSource>> {
+    let bandwidth_settings = &settings.node_stats.bandwidth;
+    let smoothing = bandwidth_settings.smoothing;
+    let update_interval = bandwidth_settings.update_interval;
+    // a single ticker reads one consistent snapshot of all interfaces
+    let counters_snapshot = match bandwidth_settings.counter_source {
+        CounterSourceKind::ProcNetDev => Some(CountersSnapshot::start(
+            ProcNetDev::new(&bandwidth_settings.proc_root),
+            update_interval,
+        )),
+        CounterSourceKind::Netlink => Some(CountersSnapshot::start(
+            NetlinkLinkStats::new(),
+            update_interval,
+        )),
+        CounterSourceKind::Sysfs => None,
+    };
+    let interfaces = bandwidth_settings
+        .interfaces
+        .iter()
+        .map(|interface| {
+            let mut link = LinkOptions::default().with_counter_width(interface.counter_width);
+
+            if let Some(speed_file) = &interface.speed_file {
+                link = link.with_speed_file(speed_file);
+            }
+
+            if let Some(capacity_bps) = interface.capacity_bps {
+                link = link.with_capacity(capacity_bps);
+            }
+
+            let provider = match &counters_snapshot {
+                Some(counters_snapshot) => CounterRateBandwidthProvider::with_smoothing(
+                    SnapshotCounterSource::new(Arc::clone(counters_snapshot), &interface.name)
+                        .with_link(link),
+                    update_interval,
+                    smoothing,
+                ),
+                None => {
+                    let mut counter_source =
+                        FileCounterSource::new(&interface.rx_file, &interface.tx_file)
+                            .with_link(link);
+
+                    if let Some(statistics_dir) = &interface.statistics_dir {
+                        counter_source = counter_source.with_statistics_dir(statistics_dir);
+                    }
+
+                    CounterRateBandwidthProvider::with_smoothing(
+                        counter_source,
+                        update_interval,
+                        smoothing,
+                    )
+                }
+            };
+
+            (interface.name.clone(), provider)
+        })
+        .collect();
+
+    let bandwidth_provider = match &bandwidth_settings.discovery {
+        Some(discovery) => MultiInterfaceBandwidthProvider::with_discovery(
+            interfaces,
+            InterfaceDiscovery {
+                sysfs_root: bandwidth_settings.sysfs_root.clone().into(),
+                pattern: discovery.pattern.clone(),
+                rescan_interval: discovery.rescan_interval,
+                update_interval,
+                counter_width: bandwidth_settings.counter_width,
+                smoothing,
+                counters_snapshot,
+            },
+        ),
+        None => MultiInterfaceBandwidthProvider::new(interfaces),
+    };
+
+    let mut data_sources: Vec<Box<dyn NodeStatsDataSource>> = vec![Box::new(bandwidth_provider)];
+
+    let cpu_settings = &settings.node_stats.cpu;
+    if cpu_settings.enabled {
+        data_sources.push(Box::new(CpuStatsProvider::new(
+            &cpu_settings.proc_root,
+            cpu_settings.update_interval,
+        )));
+    }
+
+    let memory_settings = &settings.node_stats.memory;
+    if memory_settings.enabled {
+        data_sources.push(Box::new(MemoryStatsProvider::new(
+            &memory_settings.proc_root,
+            memory_settings.update_interval,
+        )));
+    }
+
+    let load_settings = &settings.node_stats.load;
+    if load_settings.enabled {
+        data_sources.push(Box::new(LoadStatsProvider::new(
+            &load_settings.proc_root,
+            load_settings.update_interval,
+        )));
+    }
+
+    let cgroups_settings = &settings.node_stats.cgroups;
+    if cgroups_settings.enabled {
+        data_sources.push(Box::new(CgroupStatsProvider::new(
+            CgroupSelection {
+                cgroup_root: cgroups_settings.cgroup_root.clone().into(),
+                proc_root: cgroups_settings.proc_root.clone().into(),
+                pattern: cgroups_settings.pattern.clone(),
+            },
+            cgroups_settings.update_interval,
+        )));
+    }
+
+    let tcp_connections_settings = &settings.node_stats.tcp_connections;
+    if tcp_connections_settings.enabled {
+        data_sources.push(Box::new(TcpConnectionStatsProvider::new(
+            &tcp_connections_settings.proc_root,
+            tcp_connections_settings.local_ports.clone(),
+            tcp_connections_settings.update_interval,
+        )));
+    }
+
+    let filesystems_settings = &settings.node_stats.filesystems;
+    if filesystems_settings.enabled {
+        data_sources.push(Box::new(FilesystemStatsProvider::new(
+            filesystems_settings.paths.iter().map(Into::into).collect(),
+            filesystems_settings.update_interval,
+        )));
+    }
+
+    let disks_settings = &settings.node_stats.disks;
+    if disks_settings.enabled {
+        data_sources.push(Box::new(DiskStatsProvider::new(
+            &disks_settings.proc_root,
+            disks_settings.pattern.clone(),
+            disks_settings.update_interval,
+        )));
+    }
+
+    let thermal_settings = &settings.node_stats.thermal;
+    if thermal_settings.enabled {
+        data_sources.push(Box::new(ThermalStatsProvider::new(
+            &thermal_settings.sysfs_root,
+            ThermalThresholds {
+                warning_celsius: thermal_settings.warning_celsius,
+                critical_celsius: thermal_settings.critical_celsius,
+            },
+            thermal_settings.update_interval,
+        )));
+    }
+
+    let custom_settings = &settings.node_stats.custom;
+    if custom_settings.enabled {
+        data_sources.push(Box::new(CustomMetricsProvider::new(
+            custom_settings.metrics.clone(),
+            custom_settings.update_interval,
+        )));
+    }
+
+    let nginx_settings = &settings.node_stats.nginx;
+    if nginx_settings.enabled {
+        data_sources.push(Box::new(NginxStatsProvider::new(
+            nginx_settings.url.clone(),
+            nginx_settings.timeout,
+            nginx_settings.update_interval,
+        )));
+    }
+
+    data_sources
+}
+
 async fn build_tls_config(settings: &Settings) -> ServerTlsConfig {
     let tls_settings = &settings.http.tls;
 
@@ -3,10 +3,25 @@ mod http;
 mod node_stats;
 
 use error::*;
+use http::live_stats::*;
 use http::*;
 use node_stats::bandwidth::*;
+use node_stats::cgroups::*;
+use node_stats::cpu::*;
+use node_stats::custom::*;
+use node_stats::disks::*;
+use node_stats::filesystems::*;
+use node_stats::load::*;
+use node_stats::memory::*;
+use node_stats::nginx::*;
+use node_stats::tcp_connections::*;
+use node_stats::thermal::*;
 use node_stats::*;
 
+use crate::stats::bandwidth::{CounterWidth, Smoothing};
+
+pub use node_stats::bandwidth::CounterSourceKind;
+
 use std::fs::File;
 use std::io::Read;
 use std::net::{IpAddr, Ipv6Addr, SocketAddr};
@@ -74,11 +89,88 @@ impl Default for PartialSettings {
             http: Some(PartialHttp {
                 socket: Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 2351)),
                 tls: None,
+                live_stats: Some(PartialLiveStats {
+                    default_interval: Some(Duration::from_secs(1)),
+                    min_interval: Some(Duration::from_millis(100)),
+                    max_interval: Some(Duration::from_secs(60)),
+                    max_heartbeat: Some(Duration::from_secs(600)),
+                }),
             }),
             node_stats: Some(PartialNodeStats {
                 bandwidth: Some(PartialBandwidth {
                     tx_file: None,
                     rx_file: None,
+                    counter_source: Some(CounterSourceKind::Sysfs),
+                    sysfs_root: Some("/sys".into()),
+                    proc_root: Some("/proc".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                    counter_width: Some(CounterWidth::Bits64),
+                    smoothing: Some(Smoothing::default().into()),
+                    interfaces: None,
+                    discovery: Some(PartialDiscovery {
+                        glob: None,
+                        regex: None,
+                        rescan_interval: Some(Duration::from_secs(30)),
+                    }),
+                }),
+                cpu: Some(PartialCpu {
+                    enabled: Some(false),
+                    proc_root: Some("/proc".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                memory: Some(PartialMemory {
+                    enabled: Some(false),
+                    proc_root: Some("/proc".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                load: Some(PartialLoad {
+                    enabled: Some(false),
+                    proc_root: Some("/proc".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                cgroups: Some(PartialCgroups {
+                    enabled: Some(false),
+                    cgroup_root: Some("/sys/fs/cgroup".into()),
+                    proc_root: Some("/proc".into()),
+                    // docker containers with the systemd cgroup driver
+                    pattern: Some(r"^system\.slice/docker-[0-9a-f]+\.scope$".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                tcp_connections: Some(PartialTcpConnections {
+                    enabled: Some(false),
+                    proc_root: Some("/proc".into()),
+                    local_ports: Some(vec![]),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                filesystems: Some(PartialFilesystems {
+                    enabled: Some(false),
+                    paths: Some(vec!["/".into()]),
+                    update_interval: Some(Duration::from_secs(10)),
+                }),
+                disks: Some(PartialDisks {
+                    enabled: Some(false),
+                    proc_root: Some("/proc".into()),
+                    // whole devices without partitions and virtual devices
+                    pattern: Some(r"^(sd[a-z]+|vd[a-z]+|xvd[a-z]+|nvme\d+n\d+|md\d+)$".into()),
+                    update_interval: Some(Duration::from_secs(5)),
+                }),
+                thermal: Some(PartialThermal {
+                    enabled: Some(false),
+                    sysfs_root: Some("/sys".into()),
+                    warning_celsius: None,
+                    critical_celsius: None,
+                    update_interval: Some(Duration::from_secs(10)),
+                }),
+                custom: Some(PartialCustom {
+                    enabled: Some(false),
+                    update_interval: Some(Duration::from_secs(5)),
+                    command_timeout: Some(Duration::from_secs(2)),
+                    metrics: None,
+                }),
+                nginx: Some(PartialNginx {
+                    enabled: Some(false),
+                    url: Some("http://127.0.0.1/nginx_status".into()),
+                    timeout: Some(Duration::from_secs(2)),
                     update_interval: Some(Duration::from_secs(5)),
                 }),
             }),
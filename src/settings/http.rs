@@ -1,15 +1,18 @@
+pub mod live_stats;
 pub mod tls;
 
 use serde::Deserialize;
 use std::net::SocketAddr;
 
 use super::SettingsError;
+use live_stats::{LiveStats, PartialLiveStats};
 use tls::{PartialTls, Tls};
 
 #[derive(Debug)]
 pub struct Http {
     pub socket: SocketAddr,
     pub tls: Tls,
+    pub live_stats: LiveStats,
 }
 
 impl Http {
@@ -26,9 +29,15 @@ impl Http {
             .map(|s| s.unwrap())
             .collect();
 
+        let live_stats_sources = sources
+            .iter_mut()
+            .filter_map(|s| s.live_stats.take())
+            .collect();
+
         Ok(Http {
             socket: socket.ok_or_else(|| SettingsError::MissingValue("http.socket".to_string()))?,
             tls: Tls::new(tls_sources)?,
+            live_stats: LiveStats::new(live_stats_sources)?,
         })
     }
 }
@@ -37,6 +46,7 @@ impl Http {
 pub struct PartialHttp {
     pub socket: Option<SocketAddr>,
     pub tls: Option<PartialTls>,
+    pub live_stats: Option<PartialLiveStats>,
 }
 
 impl Default for PartialHttp {
@@ -44,6 +54,7 @@ impl Default for PartialHttp {
         PartialHttp {
             socket: None,
             tls: None,
+            live_stats: None,
         }
     }
 }
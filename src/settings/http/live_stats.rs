@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::SettingsError;
+
+/// Streaming intervals of the live stats, the intervals requested by the
+/// clients are clamped to the min and max interval, their heartbeats to the
+/// min interval and max heartbeat
+#[derive(Debug)]
+pub struct LiveStats {
+    pub default_interval: Duration,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    pub max_heartbeat: Duration,
+}
+
+impl LiveStats {
+    pub fn new(mut sources: Vec<PartialLiveStats>) -> Result<Self, SettingsError> {
+        let merged: PartialLiveStats =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialLiveStats {
+                    default_interval: acc.default_interval.or(x.default_interval),
+                    min_interval: acc.min_interval.or(x.min_interval),
+                    max_interval: acc.max_interval.or(x.max_interval),
+                    max_heartbeat: acc.max_heartbeat.or(x.max_heartbeat),
+                });
+
+        let live_stats = LiveStats {
+            default_interval: merged.default_interval.ok_or_else(|| {
+                SettingsError::MissingValue("http.live_stats.default_interval".into())
+            })?,
+            min_interval: merged.min_interval.ok_or_else(|| {
+                SettingsError::MissingValue("http.live_stats.min_interval".into())
+            })?,
+            max_interval: merged.max_interval.ok_or_else(|| {
+                SettingsError::MissingValue("http.live_stats.max_interval".into())
+            })?,
+            max_heartbeat: merged.max_heartbeat.ok_or_else(|| {
+                SettingsError::MissingValue("http.live_stats.max_heartbeat".into())
+            })?,
+        };
+
+        if live_stats.min_interval.as_nanos() == 0
+            || live_stats.min_interval > live_stats.default_interval
+            || live_stats.default_interval > live_stats.max_interval
+        {
+            return Err(SettingsError::Message(format!(
+                "Invalid http.live_stats intervals, expected 0 < min_interval ({:?}) <= default_interval ({:?}) <= max_interval ({:?})",
+                live_stats.min_interval, live_stats.default_interval, live_stats.max_interval
+            )));
+        }
+
+        if live_stats.max_heartbeat < live_stats.min_interval {
+            return Err(SettingsError::Message(format!(
+                "Invalid http.live_stats.max_heartbeat {:?}, expected at least min_interval ({:?})",
+                live_stats.max_heartbeat, live_stats.min_interval
+            )));
+        }
+
+        Ok(live_stats)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialLiveStats {
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub default_interval: Option<Duration>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub min_interval: Option<Duration>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub max_interval: Option<Duration>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub max_heartbeat: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_intervals() {
+        for yaml in &[
+            "default_interval: 1s\nmin_interval: 0s\nmax_interval: 60s\nmax_heartbeat: 10m\n",
+            "default_interval: 1s\nmin_interval: 2s\nmax_interval: 60s\nmax_heartbeat: 10m\n",
+            "default_interval: 1s\nmin_interval: 100ms\nmax_interval: 500ms\nmax_heartbeat: 10m\n",
+            "default_interval: 1s\nmin_interval: 100ms\nmax_interval: 60s\nmax_heartbeat: 50ms\n",
+        ] {
+            let file_settings: PartialLiveStats = serde_yaml::from_str(yaml).unwrap();
+
+            assert!(matches!(
+                LiveStats::new(vec![file_settings]),
+                Err(SettingsError::Message(_))
+            ));
+        }
+    }
+}
@@ -1,13 +1,43 @@
 pub mod bandwidth;
+pub mod cgroups;
+pub mod cpu;
+pub mod custom;
+pub mod disks;
+pub mod filesystems;
+pub mod load;
+pub mod memory;
+pub mod nginx;
+pub mod tcp_connections;
+pub mod thermal;
 
 use serde::Deserialize;
 
 use super::SettingsError;
 use bandwidth::{Bandwidth, PartialBandwidth};
+use cgroups::{Cgroups, PartialCgroups};
+use cpu::{Cpu, PartialCpu};
+use custom::{Custom, PartialCustom};
+use disks::{Disks, PartialDisks};
+use filesystems::{Filesystems, PartialFilesystems};
+use load::{Load, PartialLoad};
+use memory::{Memory, PartialMemory};
+use nginx::{Nginx, PartialNginx};
+use tcp_connections::{PartialTcpConnections, TcpConnections};
+use thermal::{PartialThermal, Thermal};
 
 #[derive(Debug)]
 pub struct NodeStats {
     pub bandwidth: Bandwidth,
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub load: Load,
+    pub cgroups: Cgroups,
+    pub tcp_connections: TcpConnections,
+    pub filesystems: Filesystems,
+    pub disks: Disks,
+    pub thermal: Thermal,
+    pub custom: Custom,
+    pub nginx: Nginx,
 }
 
 impl NodeStats {
@@ -19,8 +49,41 @@ impl NodeStats {
             .map(|s| s.unwrap())
             .collect();
 
+        let cpu_sources = sources.iter_mut().filter_map(|s| s.cpu.take()).collect();
+        let memory_sources = sources.iter_mut().filter_map(|s| s.memory.take()).collect();
+        let load_sources = sources.iter_mut().filter_map(|s| s.load.take()).collect();
+        let cgroups_sources = sources
+            .iter_mut()
+            .filter_map(|s| s.cgroups.take())
+            .collect();
+        let tcp_connections_sources = sources
+            .iter_mut()
+            .filter_map(|s| s.tcp_connections.take())
+            .collect();
+        let filesystems_sources = sources
+            .iter_mut()
+            .filter_map(|s| s.filesystems.take())
+            .collect();
+        let disks_sources = sources.iter_mut().filter_map(|s| s.disks.take()).collect();
+        let thermal_sources = sources
+            .iter_mut()
+            .filter_map(|s| s.thermal.take())
+            .collect();
+        let custom_sources = sources.iter_mut().filter_map(|s| s.custom.take()).collect();
+        let nginx_sources = sources.iter_mut().filter_map(|s| s.nginx.take()).collect();
+
         Ok(NodeStats {
             bandwidth: Bandwidth::new(bandwidth_sources)?,
+            cpu: Cpu::new(cpu_sources)?,
+            memory: Memory::new(memory_sources)?,
+            load: Load::new(load_sources)?,
+            cgroups: Cgroups::new(cgroups_sources)?,
+            tcp_connections: TcpConnections::new(tcp_connections_sources)?,
+            filesystems: Filesystems::new(filesystems_sources)?,
+            disks: Disks::new(disks_sources)?,
+            thermal: Thermal::new(thermal_sources)?,
+            custom: Custom::new(custom_sources)?,
+            nginx: Nginx::new(nginx_sources)?,
         })
     }
 }
@@ -28,10 +91,32 @@ impl NodeStats {
 #[derive(Debug, Deserialize)]
 pub struct PartialNodeStats {
     pub bandwidth: Option<PartialBandwidth>,
+    pub cpu: Option<PartialCpu>,
+    pub memory: Option<PartialMemory>,
+    pub load: Option<PartialLoad>,
+    pub cgroups: Option<PartialCgroups>,
+    pub tcp_connections: Option<PartialTcpConnections>,
+    pub filesystems: Option<PartialFilesystems>,
+    pub disks: Option<PartialDisks>,
+    pub thermal: Option<PartialThermal>,
+    pub custom: Option<PartialCustom>,
+    pub nginx: Option<PartialNginx>,
 }
 
 impl Default for PartialNodeStats {
     fn default() -> Self {
-        PartialNodeStats { bandwidth: None }
+        PartialNodeStats {
+            bandwidth: None,
+            cpu: None,
+            memory: None,
+            load: None,
+            cgroups: None,
+            tcp_connections: None,
+            filesystems: None,
+            disks: None,
+            thermal: None,
+            custom: None,
+            nginx: None,
+        }
     }
 }
@@ -1,67 +1,442 @@
-use std::rc::Rc;
+use std::collections::HashSet;
+use std::path::Path;
 use std::time::Duration;
 
+use regex::Regex;
 use serde::Deserialize;
 
 use crate::settings::SettingsError;
+use crate::stats::bandwidth::{CounterWidth, Smoothing};
 
 #[derive(Debug)]
 pub struct Bandwidth {
+    pub counter_source: CounterSourceKind,
+    pub sysfs_root: String,
+    pub proc_root: String,
+    pub update_interval: Duration,
+    pub counter_width: CounterWidth,
+    pub smoothing: Smoothing,
+    pub interfaces: Vec<Interface>,
+    pub discovery: Option<Discovery>,
+}
+
+/// Where the interface counters are read from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CounterSourceKind {
+    /// One file per counter below `<sysfs_root>/class/net/<interface>/statistics`
+    Sysfs,
+    /// A single consistent snapshot of all interfaces from `<proc_root>/net/dev`,
+    /// which rules out the counter files of the interfaces
+    ProcNetDev,
+    /// The 64 bit link statistics of all interfaces of the service's network
+    /// namespace via an RTM_GETLINK netlink dump, without any filesystem reads
+    Netlink,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Interface {
+    pub name: String,
     pub tx_file: String,
     pub rx_file: String,
-    pub update_interval: Duration,
+    /// Directory containing the packet, error and drop counters
+    pub statistics_dir: Option<String>,
+    pub speed_file: Option<String>,
+    /// Overrides the link capacity read from the speed file
+    pub capacity_bps: Option<u64>,
+    pub counter_width: CounterWidth,
 }
 
 impl Bandwidth {
-    pub fn new(sources: Vec<PartialBandwidth>) -> Result<Self, SettingsError> {
+    pub fn new(mut sources: Vec<PartialBandwidth>) -> Result<Self, SettingsError> {
         let merged: PartialBandwidth =
             sources
-                .iter()
+                .iter_mut()
                 .fold(Default::default(), |acc, x| PartialBandwidth {
-                    tx_file: acc.tx_file.or_else(|| x.tx_file.clone()),
-                    rx_file: acc.rx_file.or_else(|| x.rx_file.clone()),
+                    tx_file: acc.tx_file.or_else(|| x.tx_file.take()),
+                    rx_file: acc.rx_file.or_else(|| x.rx_file.take()),
+                    counter_source: acc.counter_source.or(x.counter_source),
+                    sysfs_root: acc.sysfs_root.or_else(|| x.sysfs_root.take()),
+                    proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
                     update_interval: acc.update_interval.or(x.update_interval),
+                    counter_width: acc.counter_width.or(x.counter_width),
+                    smoothing: PartialSmoothing::merge(acc.smoothing, x.smoothing.take()),
+                    interfaces: acc.interfaces.or_else(|| x.interfaces.take()),
+                    discovery: PartialDiscovery::merge(acc.discovery, x.discovery.take()),
                 });
 
-        let mut tx_file = merged
-            .tx_file
-            .ok_or_else(|| SettingsError::MissingValue("bandwidth.tx_file".into()))?;
-        let mut rx_file = merged
-            .rx_file
-            .ok_or_else(|| SettingsError::MissingValue("bandwidth.rx_file".into()))?;
+        let sysfs_root = merged
+            .sysfs_root
+            .ok_or_else(|| SettingsError::MissingValue("bandwidth.sysfs_root".into()))?;
+
+        let counter_width = merged
+            .counter_width
+            .ok_or_else(|| SettingsError::MissingValue("bandwidth.counter_width".into()))?;
+
+        let counter_source = merged
+            .counter_source
+            .ok_or_else(|| SettingsError::MissingValue("bandwidth.counter_source".into()))?;
+
+        let mut interfaces = merged
+            .interfaces
+            .unwrap_or_default()
+            .into_iter()
+            .map(|interface| Interface::new(interface, &sysfs_root, counter_width, counter_source))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the single tx_file/rx_file pair predates the interfaces list and
+        // has no interface name to look up in a snapshot
+        match (merged.tx_file, merged.rx_file) {
+            (Some(_), Some(_)) if counter_source != CounterSourceKind::Sysfs => {
+                return Err(SettingsError::Message(
+                    "bandwidth.tx_file and bandwidth.rx_file require the sysfs counter_source, \
+                     list the bandwidth.interfaces by name instead"
+                        .into(),
+                ))
+            }
+            (Some(tx_file), Some(rx_file)) => interfaces.push(Interface {
+                name: "default".into(),
+                tx_file,
+                rx_file,
+                statistics_dir: None,
+                speed_file: None,
+                capacity_bps: None,
+                counter_width,
+            }),
+            (Some(_), None) => return Err(SettingsError::MissingValue("bandwidth.rx_file".into())),
+            (None, Some(_)) => return Err(SettingsError::MissingValue("bandwidth.tx_file".into())),
+            (None, None) => {}
+        }
+
+        let discovery = match merged.discovery {
+            Some(discovery) => Discovery::new(discovery)?,
+            None => None,
+        };
 
-        Rc::make_mut(&mut tx_file);
-        Rc::make_mut(&mut rx_file);
+        if interfaces.is_empty() && discovery.is_none() {
+            return Err(SettingsError::MissingValue("bandwidth.interfaces".into()));
+        }
 
-        let tx_file = Rc::try_unwrap(tx_file).unwrap();
-        let rx_file = Rc::try_unwrap(rx_file).unwrap();
+        let mut names = HashSet::new();
+        if let Some(interface) = interfaces.iter().find(|i| !names.insert(i.name.as_str())) {
+            return Err(SettingsError::Message(format!(
+                "Duplicate bandwidth interface {}",
+                interface.name
+            )));
+        }
 
         Ok(Bandwidth {
-            tx_file,
-            rx_file,
+            counter_source,
+            sysfs_root,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("bandwidth.proc_root".into()))?,
             update_interval: merged
                 .update_interval
                 .ok_or_else(|| SettingsError::MissingValue("bandwidth.update_interval".into()))?,
+            counter_width,
+            smoothing: merged.smoothing.unwrap_or_default().into_smoothing()?,
+            interfaces,
+            discovery,
         })
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
+pub struct Discovery {
+    pub pattern: Regex,
+    pub rescan_interval: Duration,
+}
+
+impl Discovery {
+    /// Returns `Ok(None)` if neither a glob nor a regex pattern is configured
+    fn new(partial: PartialDiscovery) -> Result<Option<Self>, SettingsError> {
+        let pattern =
+            match (partial.glob, partial.regex) {
+                (Some(_), Some(_)) => return Err(SettingsError::Message(
+                    "Only one of bandwidth.discovery.glob and bandwidth.discovery.regex may be set"
+                        .into(),
+                )),
+                (Some(glob), None) => glob_to_regex(&glob),
+                (None, Some(regex)) => regex,
+                (None, None) => return Ok(None),
+            };
+
+        let pattern = Regex::new(&pattern).map_err(|e| {
+            SettingsError::Message(format!("Invalid bandwidth.discovery pattern: {}", e))
+        })?;
+
+        Ok(Some(Discovery {
+            pattern,
+            rescan_interval: partial.rescan_interval.ok_or_else(|| {
+                SettingsError::MissingValue("bandwidth.discovery.rescan_interval".into())
+            })?,
+        }))
+    }
+}
+
+/// Translates a shell style glob supporting `*` and `?` into an anchored regex
+fn glob_to_regex(glob: &str) -> String {
+    let pattern: String = glob
+        .split('*')
+        .map(|part| {
+            part.split('?')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect::<Vec<_>>()
+        .join(".*");
+
+    format!("^{}$", pattern)
+}
+
+impl Interface {
+    fn new(
+        partial: PartialInterface,
+        sysfs_root: &str,
+        default_counter_width: CounterWidth,
+        counter_source: CounterSourceKind,
+    ) -> Result<Self, SettingsError> {
+        let name = partial.name;
+
+        // the snapshot sources read all counters of an interface at once
+        if counter_source != CounterSourceKind::Sysfs {
+            for (key, value) in &[
+                ("tx_file", &partial.tx_file),
+                ("rx_file", &partial.rx_file),
+                ("statistics_dir", &partial.statistics_dir),
+            ] {
+                if value.is_some() {
+                    return Err(SettingsError::Message(format!(
+                        "bandwidth.interfaces.{}.{} requires the sysfs counter_source",
+                        name, key
+                    )));
+                }
+            }
+        }
+
+        let counter_width = partial.counter_width.unwrap_or(default_counter_width);
+        let interface_file = |file: &str| {
+            Path::new(sysfs_root)
+                .join("class/net")
+                .join(&name)
+                .join(file)
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        Ok(Interface {
+            tx_file: partial
+                .tx_file
+                .unwrap_or_else(|| interface_file("statistics/tx_bytes")),
+            rx_file: partial
+                .rx_file
+                .unwrap_or_else(|| interface_file("statistics/rx_bytes")),
+            statistics_dir: Some(
+                partial
+                    .statistics_dir
+                    .unwrap_or_else(|| interface_file("statistics")),
+            ),
+            speed_file: Some(
+                partial
+                    .speed_file
+                    .unwrap_or_else(|| interface_file("speed")),
+            ),
+            capacity_bps: partial.capacity_bps,
+            counter_width,
+            name,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct PartialBandwidth {
-    pub tx_file: Option<Rc<String>>,
-    pub rx_file: Option<Rc<String>>,
+    pub tx_file: Option<String>,
+    pub rx_file: Option<String>,
+    pub counter_source: Option<CounterSourceKind>,
+    pub sysfs_root: Option<String>,
+    pub proc_root: Option<String>,
 
     #[serde(default)]
     #[serde(with = "humantime_serde")]
     pub update_interval: Option<Duration>,
+
+    pub counter_width: Option<CounterWidth>,
+    pub smoothing: Option<PartialSmoothing>,
+    pub interfaces: Option<Vec<PartialInterface>>,
+    pub discovery: Option<PartialDiscovery>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialSmoothing {
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub ewma_half_life: Option<Duration>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub window: Option<Duration>,
 }
 
-impl Default for PartialBandwidth {
-    fn default() -> Self {
-        PartialBandwidth {
-            tx_file: None,
-            rx_file: None,
-            update_interval: None,
+impl PartialSmoothing {
+    fn into_smoothing(self) -> Result<Smoothing, SettingsError> {
+        Ok(Smoothing {
+            ewma_half_life: self.ewma_half_life.ok_or_else(|| {
+                SettingsError::MissingValue("bandwidth.smoothing.ewma_half_life".into())
+            })?,
+            window: self
+                .window
+                .ok_or_else(|| SettingsError::MissingValue("bandwidth.smoothing.window".into()))?,
+        })
+    }
+
+    fn merge(acc: Option<Self>, x: Option<Self>) -> Option<Self> {
+        match (acc, x) {
+            (Some(acc), Some(x)) => Some(PartialSmoothing {
+                ewma_half_life: acc.ewma_half_life.or(x.ewma_half_life),
+                window: acc.window.or(x.window),
+            }),
+            (acc, x) => acc.or(x),
         }
     }
 }
+
+impl From<Smoothing> for PartialSmoothing {
+    fn from(smoothing: Smoothing) -> Self {
+        PartialSmoothing {
+            ewma_half_life: Some(smoothing.ewma_half_life),
+            window: Some(smoothing.window),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialDiscovery {
+    pub glob: Option<String>,
+    pub regex: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub rescan_interval: Option<Duration>,
+}
+
+impl PartialDiscovery {
+    fn merge(acc: Option<Self>, x: Option<Self>) -> Option<Self> {
+        match (acc, x) {
+            (Some(acc), Some(x)) => {
+                // a pattern of a higher priority source replaces both pattern kinds
+                let has_pattern = acc.glob.is_some() || acc.regex.is_some();
+
+                Some(PartialDiscovery {
+                    glob: if has_pattern { acc.glob } else { x.glob },
+                    regex: if has_pattern { acc.regex } else { x.regex },
+                    rescan_interval: acc.rescan_interval.or(x.rescan_interval),
+                })
+            }
+            (acc, x) => acc.or(x),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PartialInterface {
+    pub name: String,
+    pub tx_file: Option<String>,
+    pub rx_file: Option<String>,
+    pub statistics_dir: Option<String>,
+    pub speed_file: Option<String>,
+    pub capacity_bps: Option<u64>,
+    pub counter_width: Option<CounterWidth>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_interfaces() {
+        let file_settings: PartialBandwidth =
+            serde_yaml::from_str("counter_source: sysfs\nsysfs_root: /sys\ncounter_width: 64\n")
+                .unwrap();
+
+        assert!(matches!(
+            Bandwidth::new(vec![file_settings]),
+            Err(SettingsError::MissingValue(path)) if path == "bandwidth.interfaces"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_discovery_pattern() {
+        let discovery = |yaml: &str| Discovery::new(serde_yaml::from_str(yaml).unwrap());
+
+        assert!(discovery("regex: \"^bond(\"\nrescan_interval: 10s\n").is_err());
+        assert!(discovery("glob: eth*\nregex: ^eth\nrescan_interval: 10s\n").is_err());
+        assert!(discovery("rescan_interval: 10s\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let pattern = Regex::new(&glob_to_regex("bond?.*")).unwrap();
+
+        assert!(pattern.is_match("bond0.100"));
+        assert!(pattern.is_match("bond1."));
+        assert!(!pattern.is_match("bond10.100"));
+        assert!(!pattern.is_match("bond0"));
+    }
+
+    #[test]
+    fn test_invalid_counter_width() {
+        assert!(serde_yaml::from_str::<PartialBandwidth>("counter_width: 16\n").is_err());
+        assert!(serde_yaml::from_str::<PartialBandwidth>(
+            "interfaces:\n  - name: eth0\n    counter_width: 8\n"
+        )
+        .is_err());
+        assert!(serde_yaml::from_str::<PartialBandwidth>("counter_width: 300\n").is_err());
+    }
+
+    #[test]
+    fn test_invalid_counter_source() {
+        assert!(serde_yaml::from_str::<PartialBandwidth>("counter_source: snmp\n").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_source_without_files() {
+        let interface = |yaml: &str| {
+            Interface::new(
+                serde_yaml::from_str(yaml).unwrap(),
+                "/sys",
+                CounterWidth::Bits64,
+                CounterSourceKind::Netlink,
+            )
+        };
+
+        assert!(interface("name: eth0\nspeed_file: /tmp/speed\n").is_ok());
+        assert!(interface("name: eth0\ntx_file: /tmp/tx\n").is_err());
+        assert!(interface("name: eth0\nstatistics_dir: /tmp/statistics\n").is_err());
+
+        let file_settings: PartialBandwidth = serde_yaml::from_str(
+            "counter_source: netlink\nsysfs_root: /sys\ncounter_width: 64\n\
+             tx_file: /tmp/tx\nrx_file: /tmp/rx\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Bandwidth::new(vec![file_settings]),
+            Err(SettingsError::Message(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_interfaces() {
+        let file_settings: PartialBandwidth = serde_yaml::from_str(
+            "counter_source: sysfs\nsysfs_root: /sys\ncounter_width: 64\n\
+             interfaces:\n  - name: eth0\n  - name: eth0\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Bandwidth::new(vec![file_settings]),
+            Err(SettingsError::Message(_))
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Cgroups {
+    pub enabled: bool,
+    pub cgroup_root: String,
+    pub proc_root: String,
+    /// Matched against the cgroup paths relative to the cgroup root
+    pub pattern: Regex,
+    pub update_interval: Duration,
+}
+
+impl Cgroups {
+    pub fn new(mut sources: Vec<PartialCgroups>) -> Result<Self, SettingsError> {
+        let merged: PartialCgroups =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialCgroups {
+                    enabled: acc.enabled.or(x.enabled),
+                    cgroup_root: acc.cgroup_root.or_else(|| x.cgroup_root.take()),
+                    proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
+                    pattern: acc.pattern.or_else(|| x.pattern.take()),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        let pattern = merged
+            .pattern
+            .ok_or_else(|| SettingsError::MissingValue("cgroups.pattern".into()))?;
+        let pattern = Regex::new(&pattern)
+            .map_err(|e| SettingsError::Message(format!("Invalid cgroups.pattern: {}", e)))?;
+
+        Ok(Cgroups {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("cgroups.enabled".into()))?,
+            cgroup_root: merged
+                .cgroup_root
+                .ok_or_else(|| SettingsError::MissingValue("cgroups.cgroup_root".into()))?,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("cgroups.proc_root".into()))?,
+            pattern,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("cgroups.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialCgroups {
+    pub enabled: Option<bool>,
+    pub cgroup_root: Option<String>,
+    pub proc_root: Option<String>,
+    pub pattern: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_pattern() {
+        let file_settings: PartialCgroups = serde_yaml::from_str("pattern: '^docker/('\n").unwrap();
+
+        assert!(matches!(
+            Cgroups::new(vec![file_settings]),
+            Err(SettingsError::Message(_))
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Cpu {
+    pub enabled: bool,
+    pub proc_root: String,
+    pub update_interval: Duration,
+}
+
+impl Cpu {
+    pub fn new(mut sources: Vec<PartialCpu>) -> Result<Self, SettingsError> {
+        let merged: PartialCpu = sources
+            .iter_mut()
+            .fold(Default::default(), |acc, x| PartialCpu {
+                enabled: acc.enabled.or(x.enabled),
+                proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
+                update_interval: acc.update_interval.or(x.update_interval),
+            });
+
+        Ok(Cpu {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("cpu.enabled".into()))?,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("cpu.proc_root".into()))?,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("cpu.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialCpu {
+    pub enabled: Option<bool>,
+    pub proc_root: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
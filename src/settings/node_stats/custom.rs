@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+use crate::stats::custom::{CustomMetric, MetricKind, MetricParser, MetricSource};
+
+#[derive(Debug)]
+pub struct Custom {
+    pub enabled: bool,
+    pub update_interval: Duration,
+    pub metrics: Vec<CustomMetric>,
+}
+
+impl Custom {
+    pub fn new(mut sources: Vec<PartialCustom>) -> Result<Self, SettingsError> {
+        let merged: PartialCustom =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialCustom {
+                    enabled: acc.enabled.or(x.enabled),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                    command_timeout: acc.command_timeout.or(x.command_timeout),
+                    metrics: acc.metrics.or_else(|| x.metrics.take()),
+                });
+
+        let command_timeout = merged
+            .command_timeout
+            .ok_or_else(|| SettingsError::MissingValue("custom.command_timeout".into()))?;
+
+        let metrics = merged
+            .metrics
+            .unwrap_or_default()
+            .into_iter()
+            .map(|metric| metric.into_metric(command_timeout))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut names = HashSet::new();
+        if let Some(metric) = metrics.iter().find(|m| !names.insert(m.name.as_str())) {
+            return Err(SettingsError::Message(format!(
+                "Duplicate custom metric {}",
+                metric.name
+            )));
+        }
+
+        Ok(Custom {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("custom.enabled".into()))?,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("custom.update_interval".into()))?,
+            metrics,
+        })
+    }
+}
+
+impl PartialCustomMetric {
+    fn into_metric(self, default_command_timeout: Duration) -> Result<CustomMetric, SettingsError> {
+        let name = self.name;
+
+        let source = match (self.file, self.command) {
+            (Some(file), None) => MetricSource::File(file),
+            (None, Some(command)) if !command.is_empty() => MetricSource::Command {
+                command,
+                timeout: self.timeout.unwrap_or(default_command_timeout),
+            },
+            (None, Some(_)) => {
+                return Err(SettingsError::Message(format!(
+                    "Empty custom.metrics.{}.command",
+                    name
+                )))
+            }
+            _ => {
+                return Err(SettingsError::Message(format!(
+                "Exactly one of custom.metrics.{0}.file and custom.metrics.{0}.command must be set",
+                name
+            )))
+            }
+        };
+
+        if let MetricParser::JsonPointer(pointer) = &self.parser {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                return Err(SettingsError::Message(format!(
+                    "Invalid custom.metrics.{}.parser JSON pointer {}, expected a leading /",
+                    name, pointer
+                )));
+            }
+        }
+
+        Ok(CustomMetric {
+            source,
+            parser: self.parser,
+            kind: self.kind.unwrap_or(MetricKind::Gauge),
+            name,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialCustom {
+    pub enabled: Option<bool>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+
+    /// Applies to the command metrics without their own timeout
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub command_timeout: Option<Duration>,
+
+    pub metrics: Option<Vec<PartialCustomMetric>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PartialCustomMetric {
+    pub name: String,
+    pub file: Option<PathBuf>,
+    pub command: Option<Vec<String>>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+
+    pub parser: MetricParser,
+    pub kind: Option<MetricKind>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_metrics() {
+        let invalid = |metric: &str| {
+            let metric: PartialCustomMetric = serde_yaml::from_str(metric).unwrap();
+
+            metric.into_metric(Duration::from_secs(2)).is_err()
+        };
+
+        assert!(invalid("{name: a, parser: integer}"));
+        assert!(invalid(
+            "{name: a, file: /a, command: [/a], parser: integer}"
+        ));
+        assert!(invalid("{name: a, command: [], parser: integer}"));
+        assert!(invalid("{name: a, file: /a, parser: {json_pointer: a/b}}"));
+        assert!(!invalid(
+            "{name: a, file: /a, parser: {json_pointer: /a/b}}"
+        ));
+
+        let file_settings: PartialCustom = serde_yaml::from_str(
+            "command_timeout: 2s\nmetrics:\n  - {name: a, file: /a, parser: integer}\n  - {name: a, file: /b, parser: float}\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Custom::new(vec![file_settings]),
+            Err(SettingsError::Message(_))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Disks {
+    pub enabled: bool,
+    pub proc_root: String,
+    /// Matched against the device names, e.g. to leave out partitions
+    pub pattern: Regex,
+    pub update_interval: Duration,
+}
+
+impl Disks {
+    pub fn new(mut sources: Vec<PartialDisks>) -> Result<Self, SettingsError> {
+        let merged: PartialDisks =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialDisks {
+                    enabled: acc.enabled.or(x.enabled),
+                    proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
+                    pattern: acc.pattern.or_else(|| x.pattern.take()),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        let pattern = merged
+            .pattern
+            .ok_or_else(|| SettingsError::MissingValue("disks.pattern".into()))?;
+        let pattern = Regex::new(&pattern)
+            .map_err(|e| SettingsError::Message(format!("Invalid disks.pattern: {}", e)))?;
+
+        Ok(Disks {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("disks.enabled".into()))?,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("disks.proc_root".into()))?,
+            pattern,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("disks.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialDisks {
+    pub enabled: Option<bool>,
+    pub proc_root: Option<String>,
+    pub pattern: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
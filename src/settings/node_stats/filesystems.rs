@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Filesystems {
+    pub enabled: bool,
+    /// Reported are the filesystems containing these paths
+    pub paths: Vec<String>,
+    pub update_interval: Duration,
+}
+
+impl Filesystems {
+    pub fn new(mut sources: Vec<PartialFilesystems>) -> Result<Self, SettingsError> {
+        let merged: PartialFilesystems =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialFilesystems {
+                    enabled: acc.enabled.or(x.enabled),
+                    paths: acc.paths.or_else(|| x.paths.take()),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        Ok(Filesystems {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("filesystems.enabled".into()))?,
+            paths: merged
+                .paths
+                .ok_or_else(|| SettingsError::MissingValue("filesystems.paths".into()))?,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("filesystems.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialFilesystems {
+    pub enabled: Option<bool>,
+    pub paths: Option<Vec<String>>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
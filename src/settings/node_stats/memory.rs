@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Memory {
+    pub enabled: bool,
+    pub proc_root: String,
+    pub update_interval: Duration,
+}
+
+impl Memory {
+    pub fn new(mut sources: Vec<PartialMemory>) -> Result<Self, SettingsError> {
+        let merged: PartialMemory =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialMemory {
+                    enabled: acc.enabled.or(x.enabled),
+                    proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        Ok(Memory {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("memory.enabled".into()))?,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("memory.proc_root".into()))?,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("memory.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialMemory {
+    pub enabled: Option<bool>,
+    pub proc_root: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use hyper::Uri;
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Nginx {
+    pub enabled: bool,
+    /// Location of the stub_status page
+    pub url: Uri,
+    pub timeout: Duration,
+    pub update_interval: Duration,
+}
+
+impl Nginx {
+    pub fn new(mut sources: Vec<PartialNginx>) -> Result<Self, SettingsError> {
+        let merged: PartialNginx =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialNginx {
+                    enabled: acc.enabled.or(x.enabled),
+                    url: acc.url.or_else(|| x.url.take()),
+                    timeout: acc.timeout.or(x.timeout),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        let url = merged
+            .url
+            .ok_or_else(|| SettingsError::MissingValue("nginx.url".into()))?;
+        let url = url
+            .parse::<Uri>()
+            .map_err(|e| SettingsError::Message(format!("Invalid nginx.url {}: {}", url, e)))?;
+
+        Ok(Nginx {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("nginx.enabled".into()))?,
+            url,
+            timeout: merged
+                .timeout
+                .ok_or_else(|| SettingsError::MissingValue("nginx.timeout".into()))?,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("nginx.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialNginx {
+    pub enabled: Option<bool>,
+    pub url: Option<String>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct TcpConnections {
+    pub enabled: bool,
+    pub proc_root: String,
+    /// Empty to count the sockets of all local ports
+    pub local_ports: Vec<u16>,
+    pub update_interval: Duration,
+}
+
+impl TcpConnections {
+    pub fn new(mut sources: Vec<PartialTcpConnections>) -> Result<Self, SettingsError> {
+        let merged: PartialTcpConnections =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialTcpConnections {
+                    enabled: acc.enabled.or(x.enabled),
+                    proc_root: acc.proc_root.or_else(|| x.proc_root.take()),
+                    local_ports: acc.local_ports.or_else(|| x.local_ports.take()),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        Ok(TcpConnections {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("tcp_connections.enabled".into()))?,
+            proc_root: merged
+                .proc_root
+                .ok_or_else(|| SettingsError::MissingValue("tcp_connections.proc_root".into()))?,
+            local_ports: merged
+                .local_ports
+                .ok_or_else(|| SettingsError::MissingValue("tcp_connections.local_ports".into()))?,
+            update_interval: merged.update_interval.ok_or_else(|| {
+                SettingsError::MissingValue("tcp_connections.update_interval".into())
+            })?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialTcpConnections {
+    pub enabled: Option<bool>,
+    pub proc_root: Option<String>,
+    pub local_ports: Option<Vec<u16>>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::settings::SettingsError;
+
+#[derive(Debug)]
+pub struct Thermal {
+    pub enabled: bool,
+    pub sysfs_root: String,
+    pub warning_celsius: Option<f64>,
+    pub critical_celsius: Option<f64>,
+    pub update_interval: Duration,
+}
+
+impl Thermal {
+    pub fn new(mut sources: Vec<PartialThermal>) -> Result<Self, SettingsError> {
+        let merged: PartialThermal =
+            sources
+                .iter_mut()
+                .fold(Default::default(), |acc, x| PartialThermal {
+                    enabled: acc.enabled.or(x.enabled),
+                    sysfs_root: acc.sysfs_root.or_else(|| x.sysfs_root.take()),
+                    warning_celsius: acc.warning_celsius.or(x.warning_celsius),
+                    critical_celsius: acc.critical_celsius.or(x.critical_celsius),
+                    update_interval: acc.update_interval.or(x.update_interval),
+                });
+
+        if let (Some(warning), Some(critical)) = (merged.warning_celsius, merged.critical_celsius) {
+            if warning > critical {
+                return Err(SettingsError::Message(format!(
+                    "thermal.warning_celsius {} is above thermal.critical_celsius {}",
+                    warning, critical
+                )));
+            }
+        }
+
+        Ok(Thermal {
+            enabled: merged
+                .enabled
+                .ok_or_else(|| SettingsError::MissingValue("thermal.enabled".into()))?,
+            sysfs_root: merged
+                .sysfs_root
+                .ok_or_else(|| SettingsError::MissingValue("thermal.sysfs_root".into()))?,
+            warning_celsius: merged.warning_celsius,
+            critical_celsius: merged.critical_celsius,
+            update_interval: merged
+                .update_interval
+                .ok_or_else(|| SettingsError::MissingValue("thermal.update_interval".into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PartialThermal {
+    pub enabled: Option<bool>,
+    pub sysfs_root: Option<String>,
+    pub warning_celsius: Option<f64>,
+    pub critical_celsius: Option<f64>,
+
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub update_interval: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warning_above_critical() {
+        let file_settings: PartialThermal =
+            serde_yaml::from_str("warning_celsius: 95\ncritical_celsius: 80\n").unwrap();
+
+        assert!(matches!(
+            Thermal::new(vec![file_settings]),
+            Err(SettingsError::Message(_))
+        ));
+    }
+}
@@ -1,27 +1,74 @@
 pub mod bandwidth;
-
+pub mod cgroup;
+pub mod cpu;
+pub mod custom;
+pub mod disk;
+pub mod filesystem;
+pub mod load;
+pub mod memory;
+pub mod nginx;
+pub mod tcp_connections;
+pub mod thermal;
+
+use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::{Arc, RwLock, Weak};
+use std::time::SystemTime;
 
 use log::info;
-use tokio::sync::watch::Receiver;
+use tokio::sync::watch::{self, Receiver};
 
 use crate::util::TraitDisplay;
 use bandwidth::*;
+use cgroup::CgroupStats;
+use cpu::CpuStats;
+use custom::CustomMetrics;
+use disk::DiskStats;
+use filesystem::FilesystemStats;
+use load::LoadStats;
+use memory::MemoryStats;
+use nginx::NginxStats;
+use tcp_connections::TcpStateCounts;
+use thermal::ThermalStats;
 
 #[derive(Debug, Default, Clone)]
 pub struct NodeStats {
     pub bandwidth: Arc<Bandwidth>,
+    pub interface_bandwidth: Arc<InterfaceBandwidth>,
+    pub cpu: Option<Arc<CpuStats>>,
+    pub memory: Option<Arc<MemoryStats>>,
+    pub load: Option<Arc<LoadStats>>,
+    pub cgroups: Option<Arc<Vec<CgroupStats>>>,
+    pub tcp_connections: Option<Arc<TcpStateCounts>>,
+    pub filesystems: Option<Arc<Vec<FilesystemStats>>>,
+    pub disks: Option<Arc<Vec<DiskStats>>>,
+    pub thermal: Option<Arc<ThermalStats>>,
+    pub custom: Option<Arc<CustomMetrics>>,
+    pub nginx: Option<Arc<NginxStats>>,
+    /// Read time of the latest measured data of any of the data sources
+    pub updated_at: Option<SystemTime>,
+    /// Read time of the latest measured data of each of the data sources
+    pub source_updated_at: Arc<BTreeMap<&'static str, SystemTime>>,
 }
 
 pub trait NodeStatsUpdater: Send + Sync {
     fn update_node_stats(&self, node_stats: NodeStats) -> NodeStats;
 }
 
+/// Sent by a data source after updating its stats, carries the time the stats
+/// were read. None for updates without measured data, like the initial value
+/// of the channel.
+pub type UpdateNotification = Option<SystemTime>;
+
 pub trait NodeStatsUpdateNotifier: Send + Sync {
-    fn get_update_channel_receiver(&self) -> Receiver<()>;
+    fn get_update_channel_receiver(&self) -> Receiver<UpdateNotification>;
 }
 
+/// A data source whose read fails clears its stats and sends None instead of
+/// keeping the last ones, stale stats would hide that the data is missing.
+/// Sources reading several items, like filesystems or custom metrics, only
+/// leave out the items that failed. The bandwidth is the exception, a failed
+/// read of the counters keeps the last ones so the next rate covers the gap.
 pub trait NodeStatsDataSource: NodeStatsUpdater + NodeStatsUpdateNotifier {
     fn get_name(&self) -> &'static str;
 }
@@ -37,17 +84,22 @@ where
 
 pub struct NodeStatsProvider {
     node_stats: Arc<RwLock<Arc<NodeStats>>>,
+    update_receiver: Receiver<Arc<NodeStats>>,
 }
 
 impl NodeStatsProvider {
     pub fn new(updaters: Vec<Box<dyn NodeStatsDataSource>>) -> Self {
-        let shared_node_stats = Arc::new(RwLock::new(Arc::new(Default::default())));
+        let initial_node_stats = Arc::new(NodeStats::default());
+        let shared_node_stats = Arc::new(RwLock::new(Arc::clone(&initial_node_stats)));
+
+        let (tx, rx) = watch::channel(initial_node_stats);
 
         let provider = Self {
             node_stats: Arc::clone(&shared_node_stats),
+            update_receiver: rx,
         };
 
-        start_update_loop(Arc::downgrade(&shared_node_stats), updaters);
+        start_update_loop(Arc::downgrade(&shared_node_stats), Arc::new(tx), updaters);
 
         provider
     }
@@ -55,10 +107,17 @@ impl NodeStatsProvider {
     pub fn current_node_stats(&self) -> Arc<NodeStats> {
         Arc::clone(&self.node_stats.read().unwrap())
     }
+
+    /// Receives the node stats after every update by one of the data sources,
+    /// starting with the current ones. Slow receivers only get the latest stats.
+    pub fn subscribe(&self) -> Receiver<Arc<NodeStats>> {
+        self.update_receiver.clone()
+    }
 }
 
 fn start_update_loop(
     node_stats: Weak<RwLock<Arc<NodeStats>>>,
+    update_sender: Arc<watch::Sender<Arc<NodeStats>>>,
     updaters: Vec<Box<dyn NodeStatsDataSource>>,
 ) {
     info!("Start NodeStatsProvider update loop");
@@ -66,16 +125,12 @@ fn start_update_loop(
     for updater in updaters {
         let mut update_notification_rx = updater.get_update_channel_receiver();
         let node_stats = node_stats.clone();
+        let update_sender = Arc::clone(&update_sender);
 
         tokio::spawn(async move {
-            loop {
-                if await_update_notification(&mut update_notification_rx, updater.as_ref())
-                    .await
-                    .is_none()
-                {
-                    break;
-                }
-
+            while let Some(read_at) =
+                await_update_notification(&mut update_notification_rx, updater.as_ref()).await
+            {
                 let node_stats = match node_stats.upgrade() {
                     Some(node_stats) => node_stats,
                     None => {
@@ -85,7 +140,17 @@ fn start_update_loop(
                 };
 
                 let mut ns_lock_guard = node_stats.write().unwrap();
-                *ns_lock_guard = Arc::new(updater.update_node_stats((**ns_lock_guard).clone()));
+                let mut updated_node_stats = updater.update_node_stats((**ns_lock_guard).clone());
+                if let Some(read_at) = read_at {
+                    let source_updated_at =
+                        Arc::make_mut(&mut updated_node_stats.source_updated_at);
+                    source_updated_at.insert(updater.get_name(), read_at);
+                    updated_node_stats.updated_at = source_updated_at.values().max().copied();
+                }
+                *ns_lock_guard = Arc::new(updated_node_stats);
+
+                // sent while holding the lock to keep the order of the updates
+                let _ = update_sender.broadcast(Arc::clone(&ns_lock_guard));
             }
         });
     }
@@ -94,20 +159,95 @@ fn start_update_loop(
 }
 
 async fn await_update_notification<T: NodeStatsDataSource + ?Sized>(
-    channel: &mut Receiver<()>,
+    channel: &mut Receiver<UpdateNotification>,
     updater: &T,
-) -> Option<()> {
-    if channel.recv().await.is_none() {
+) -> Option<UpdateNotification> {
+    let notification = channel.recv().await;
+
+    if notification.is_none() {
         info!(
             "Received none over the update notification channel from {}, stop listening",
             TraitDisplay(updater)
         );
-        None
     } else {
         info!(
             "Received updater notification from {}",
             TraitDisplay(updater)
         );
-        Some(())
+    }
+
+    notification
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockDataSource {
+        update_receiver: Receiver<UpdateNotification>,
+    }
+
+    impl NodeStatsUpdater for MockDataSource {
+        fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+            node_stats.cpu = Some(Arc::new(CpuStats::default()));
+
+            node_stats
+        }
+    }
+
+    impl NodeStatsUpdateNotifier for MockDataSource {
+        fn get_update_channel_receiver(&self) -> Receiver<UpdateNotification> {
+            self.update_receiver.clone()
+        }
+    }
+
+    impl NodeStatsDataSource for MockDataSource {
+        fn get_name(&self) -> &'static str {
+            "MockDataSource"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let (update_sender, update_receiver) = watch::channel(None);
+        let provider = NodeStatsProvider::new(vec![Box::new(MockDataSource { update_receiver })]);
+
+        let mut subscription = provider.subscribe();
+        let initial_node_stats = subscription.recv().await.unwrap();
+        assert!(initial_node_stats.updated_at.is_none());
+
+        // the provider consumes the initial notification right away
+        let node_stats = subscription.recv().await.unwrap();
+        assert!(node_stats.cpu.is_some());
+        assert!(node_stats.updated_at.is_none());
+        assert!(node_stats.source_updated_at.is_empty());
+
+        let read_at = SystemTime::now();
+        update_sender.broadcast(Some(read_at)).unwrap();
+        let node_stats = subscription.recv().await.unwrap();
+        assert!(Arc::ptr_eq(&node_stats, &provider.current_node_stats()));
+        assert_eq!(Some(read_at), node_stats.updated_at);
+        assert_eq!(
+            Some(&read_at),
+            node_stats.source_updated_at.get("MockDataSource")
+        );
+
+        // updates without measured data keep the time of the last reading
+        update_sender.broadcast(None).unwrap();
+        let node_stats = subscription.recv().await.unwrap();
+        assert_eq!(Some(read_at), node_stats.updated_at);
+    }
+
+    #[tokio::test]
+    async fn test_reading_before_subscribing() {
+        let read_at = SystemTime::now();
+        let (_update_sender, update_receiver) = watch::channel(Some(read_at));
+        let provider = NodeStatsProvider::new(vec![Box::new(MockDataSource { update_receiver })]);
+
+        let mut subscription = provider.subscribe();
+        subscription.recv().await.unwrap();
+
+        let node_stats = subscription.recv().await.unwrap();
+        assert_eq!(Some(read_at), node_stats.updated_at);
     }
 }
@@ -1,29 +1,123 @@
 mod counter_rate;
+mod multi_interface;
 mod random;
 
+use std::collections::BTreeMap;
+use std::ops::Add;
 use std::sync::Arc;
 
 use super::{NodeStats, NodeStatsUpdater};
 
-pub use counter_rate::counter_source::FileCounterSource;
-pub use counter_rate::CounterRateBandwidthProvider;
+pub use counter_rate::counter_source::{
+    CounterSource, CounterWidth, CountersSnapshot, FileCounterSource, InterfaceCounters,
+    InterfaceCountersReader, LinkOptions, NetlinkLinkStats, ProcNetDev, SnapshotCounterSource,
+};
+pub(crate) use counter_rate::{calc_counter_delta, CounterDelta};
+pub use counter_rate::{CounterRateBandwidthProvider, Smoothing};
+use counter_rate::{RateHistory, RateSample};
+pub use multi_interface::{
+    InterfaceDiscovery, MultiInterfaceBandwidth, MultiInterfaceBandwidthProvider,
+};
 pub use random::RandomBandwidthProvider;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Bandwidth {
     pub tx_bps: u64,
     pub rx_bps: u64,
+    /// Link capacity in bits per second, absent if unknown
+    pub capacity_bps: Option<u64>,
+    /// Ratio of the used to the available capacity
+    pub tx_utilization: Option<f64>,
+    pub rx_utilization: Option<f64>,
+    /// Marks a sample taken right after a counter reset, its rates only cover
+    /// the time since the reset
+    pub counter_reset: bool,
+    /// Number of counter resets observed since the start
+    pub counter_resets: u64,
+    /// Exponentially weighted moving averages of the rates
+    pub tx_ewma_bps: u64,
+    pub rx_ewma_bps: u64,
+    /// Mean and max rates within the sliding window
+    pub tx_window_mean_bps: u64,
+    pub rx_window_mean_bps: u64,
+    pub tx_window_max_bps: u64,
+    pub rx_window_max_bps: u64,
+    pub percentiles: BandwidthPercentiles,
+    pub packet_rates: PacketRates,
 }
 
-impl Default for Bandwidth {
-    fn default() -> Self {
+/// Packet, error and drop rates per second
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PacketRates {
+    pub rx_packets: f64,
+    pub tx_packets: f64,
+    pub rx_errors: f64,
+    pub tx_errors: f64,
+    pub rx_dropped: f64,
+    pub tx_dropped: f64,
+    pub rx_fifo_errors: f64,
+    pub tx_fifo_errors: f64,
+    pub collisions: f64,
+}
+
+/// Rate percentiles and peaks over the last 1, 5 and 15 minutes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BandwidthPercentiles {
+    pub one: WindowPercentiles,
+    pub five: WindowPercentiles,
+    pub fifteen: WindowPercentiles,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WindowPercentiles {
+    pub tx: RatePercentiles,
+    pub rx: RatePercentiles,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RatePercentiles {
+    pub p50_bps: u64,
+    pub p95_bps: u64,
+    pub p99_bps: u64,
+    pub peak_bps: u64,
+}
+
+impl Add for &PacketRates {
+    type Output = PacketRates;
+
+    fn add(self, other: Self) -> PacketRates {
+        PacketRates {
+            rx_packets: self.rx_packets + other.rx_packets,
+            tx_packets: self.tx_packets + other.tx_packets,
+            rx_errors: self.rx_errors + other.rx_errors,
+            tx_errors: self.tx_errors + other.tx_errors,
+            rx_dropped: self.rx_dropped + other.rx_dropped,
+            tx_dropped: self.tx_dropped + other.tx_dropped,
+            rx_fifo_errors: self.rx_fifo_errors + other.rx_fifo_errors,
+            tx_fifo_errors: self.tx_fifo_errors + other.tx_fifo_errors,
+            collisions: self.collisions + other.collisions,
+        }
+    }
+}
+
+impl Bandwidth {
+    pub fn with_capacity(self, capacity_bps: Option<u64>) -> Self {
+        let capacity_bps = capacity_bps.filter(|capacity_bps| *capacity_bps > 0);
+        let utilization =
+            |bps: u64| capacity_bps.map(|capacity_bps| bps as f64 / capacity_bps as f64);
+
         Self {
-            tx_bps: 0,
-            rx_bps: 0,
+            capacity_bps,
+            tx_utilization: utilization(self.tx_bps),
+            rx_utilization: utilization(self.rx_bps),
+            ..self
         }
     }
 }
 
+/// Bandwidth per network interface name
+pub type InterfaceBandwidth = BTreeMap<String, Arc<Bandwidth>>;
+
 pub trait BandwidthProvider: Send + Sync {
     fn current_bandwidth(&self) -> Arc<Bandwidth>;
 }
@@ -35,3 +129,34 @@ impl<T: BandwidthProvider> NodeStatsUpdater for T {
         node_stats
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_with_capacity() {
+        let bandwidth = Bandwidth {
+            tx_bps: 250_000_000,
+            rx_bps: 500_000_000,
+            ..Default::default()
+        }
+        .with_capacity(Some(1_000_000_000));
+
+        assert_eq!(Some(1_000_000_000), bandwidth.capacity_bps);
+        assert_eq!(Some(0.25), bandwidth.tx_utilization);
+        assert_eq!(Some(0.5), bandwidth.rx_utilization);
+    }
+
+    #[test]
+    fn test_bandwidth_with_unknown_capacity() {
+        let bandwidth = || Bandwidth {
+            tx_bps: 1,
+            rx_bps: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(bandwidth(), bandwidth().with_capacity(None));
+        assert_eq!(bandwidth(), bandwidth().with_capacity(Some(0)));
+    }
+}
@@ -1,32 +1,48 @@
 pub mod counter_source;
+mod percentiles;
+mod smoothing;
 
+use std::convert::TryFrom;
 use std::sync::{Arc, RwLock, Weak};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use log::{info, trace};
+use log::{info, trace, warn};
 use tokio::sync::watch;
 use tokio::time;
 
-use super::{Bandwidth, BandwidthProvider};
-use crate::stats::{NodeStatsDataSource, NodeStatsUpdateNotifier};
-use counter_source::CounterSource;
+use super::{Bandwidth, BandwidthProvider, PacketRates};
+use crate::stats::{NodeStatsDataSource, NodeStatsUpdateNotifier, UpdateNotification};
+use counter_source::{CounterSource, CounterWidth, InterfaceCounters};
+use smoothing::BandwidthSmoother;
+
+pub(super) use percentiles::{RateHistory, RateSample};
+pub use smoothing::Smoothing;
 
 pub struct CounterRateBandwidthProvider {
     bandwidth: Arc<RwLock<Arc<Bandwidth>>>,
-    update_receiver: watch::Receiver<()>,
+    update_receiver: watch::Receiver<UpdateNotification>,
 }
 
-#[derive(Default, Debug)]
-struct CounterValues {
-    rx: u64,
-    tx: u64,
+#[derive(Debug, PartialEq)]
+pub(crate) enum CounterDelta {
+    Increase(u64),
+    /// The counter restarted, e.g. due to a driver reload, carries the current value
+    Reset(u64),
 }
 
 impl CounterRateBandwidthProvider {
     pub fn new<T: CounterSource + 'static>(source: T, update_interval: Duration) -> Self {
+        Self::with_smoothing(source, update_interval, Default::default())
+    }
+
+    pub fn with_smoothing<T: CounterSource + 'static>(
+        source: T,
+        update_interval: Duration,
+        smoothing: Smoothing,
+    ) -> Self {
         let shared_bandwidth = Arc::new(RwLock::new(Arc::new(Default::default())));
 
-        let (tx, rx) = watch::channel(());
+        let (tx, rx) = watch::channel(None);
 
         let provider = Self {
             bandwidth: Arc::clone(&shared_bandwidth),
@@ -38,6 +54,7 @@ impl CounterRateBandwidthProvider {
             tx,
             source,
             update_interval,
+            smoothing,
         );
 
         provider
@@ -51,7 +68,7 @@ impl BandwidthProvider for CounterRateBandwidthProvider {
 }
 
 impl NodeStatsUpdateNotifier for CounterRateBandwidthProvider {
-    fn get_update_channel_receiver(&self) -> watch::Receiver<()> {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
         self.update_receiver.clone()
     }
 }
@@ -64,30 +81,44 @@ impl NodeStatsDataSource for CounterRateBandwidthProvider {
 
 fn start_update_loop<T: CounterSource + 'static>(
     bandwidth: Weak<RwLock<Arc<Bandwidth>>>,
-    update_sender: watch::Sender<()>,
+    update_sender: watch::Sender<UpdateNotification>,
     counter_source: T,
     update_interval: Duration,
+    smoothing: Smoothing,
 ) {
     info!("Start CounterRateBandwidthProvider update loop");
 
     tokio::spawn(async move {
-        update_loop(bandwidth, update_sender, counter_source, update_interval).await
+        update_loop(
+            bandwidth,
+            update_sender,
+            counter_source,
+            update_interval,
+            smoothing,
+        )
+        .await
     });
 }
 
 async fn update_loop<T: CounterSource>(
     bandwidth: Weak<RwLock<Arc<Bandwidth>>>,
-    update_sender: watch::Sender<()>,
+    update_sender: watch::Sender<UpdateNotification>,
     counter_source: T,
     update_interval: Duration,
+    smoothing: Smoothing,
 ) {
-    let mut interval = time::interval(update_interval);
+    let mut ticker = match counter_source.subscribe() {
+        Some(updates) => Ticker::Updates(updates),
+        None => Ticker::Interval(time::interval(update_interval)),
+    };
     let mut last_time: Option<Instant> = None;
-    let mut last_counter_values: CounterValues = Default::default();
-
-    interval.tick().await; // the first tick will complete immediately
+    let mut last_counters: InterfaceCounters = Default::default();
+    let mut counter_resets = 0;
+    let mut smoother = BandwidthSmoother::new(smoothing);
+    let mut rate_history = RateHistory::new(smoothing.window);
 
-    loop {
+    // the first tick will complete immediately, or with the first shared snapshot
+    while ticker.tick().await {
         let bandwidth = match bandwidth.upgrade() {
             Some(bandwidth) => bandwidth,
             None => {
@@ -96,40 +127,89 @@ async fn update_loop<T: CounterSource>(
             }
         };
 
-        let current_counter_values = CounterValues {
-            rx: counter_source.get_rx(),
-            tx: counter_source.get_tx(),
+        // a failed read keeps the last counters, the next rate then covers
+        // the skipped interval instead of showing a reset and a spike
+        let current_counters = match counter_source.get_counters() {
+            Ok(counters) => counters,
+            Err(e) => {
+                warn!("Failed to get the interface counters: {:?}", e);
+
+                continue;
+            }
         };
         let current_time = Instant::now();
 
-        if let Some(new_bandwidth) = calc_bandwidth(
-            &current_counter_values,
-            &last_counter_values,
+        if let Some((mut new_bandwidth, elapsed)) = calc_bandwidth(
+            &current_counters,
+            &last_counters,
             &last_time,
             &current_time,
+            counter_source.get_counter_width(),
         ) {
+            if new_bandwidth.counter_reset {
+                counter_resets += 1;
+                info!(
+                    "Detected counter reset, {} resets so far {:?} {:?}",
+                    counter_resets, current_counters, last_counters
+                );
+            }
+            new_bandwidth.counter_resets = counter_resets;
+
+            rate_history.push(RateSample {
+                time: current_time,
+                elapsed,
+                tx_bps: new_bandwidth.tx_bps,
+                rx_bps: new_bandwidth.rx_bps,
+            });
+            new_bandwidth.percentiles = rate_history.percentiles();
+
+            let new_bandwidth = smoother
+                .smooth(elapsed, new_bandwidth, &rate_history)
+                .with_capacity(counter_source.get_capacity());
             *bandwidth.write().unwrap() = Arc::new(new_bandwidth);
-            update_sender.broadcast(()).unwrap();
+            update_sender.broadcast(Some(SystemTime::now())).unwrap();
         }
 
         last_time = Some(current_time);
-        last_counter_values = current_counter_values;
+        last_counters = current_counters;
+    }
+}
+
+/// Paces the update loop, counter sources sharing a snapshot of all
+/// interfaces pace it with their snapshot updates instead of an interval
+enum Ticker {
+    Interval(time::Interval),
+    Updates(watch::Receiver<()>),
+}
 
-        interval.tick().await;
+impl Ticker {
+    /// Returns false once the counter source stopped updating
+    async fn tick(&mut self) -> bool {
+        match self {
+            Ticker::Interval(interval) => {
+                interval.tick().await;
+                true
+            }
+            Ticker::Updates(updates) => {
+                let updated = updates.recv().await.is_some();
+                if !updated {
+                    info!("Counter source stopped updating, ending update loop");
+                }
+
+                updated
+            }
+        }
     }
 }
 
 fn calc_bandwidth(
-    current_counter_values: &CounterValues,
-    last_counter_values: &CounterValues,
+    current_counters: &InterfaceCounters,
+    last_counters: &InterfaceCounters,
     last_time: &Option<Instant>,
     current_time: &Instant,
-) -> Option<Bandwidth> {
-    if last_time.is_none() {
-        return None;
-    }
-
-    let last_time = last_time.unwrap();
+    counter_width: CounterWidth,
+) -> Option<(Bandwidth, Duration)> {
+    let last_time = (*last_time)?;
 
     trace!(
         "Calculating bandwidth last_time: {:?} current_time: {:?}",
@@ -137,168 +217,545 @@ fn calc_bandwidth(
         current_time
     );
 
-    if current_counter_values.rx < last_counter_values.rx
-        || current_counter_values.tx < last_counter_values.tx
-    {
-        trace!(
-            "Current counter values are smaller than the last counter values {:?} {:?}",
-            current_counter_values,
-            last_counter_values
-        );
+    let elapsed = current_time.duration_since(last_time);
+    if elapsed.as_nanos() == 0 {
+        trace!("No time elapsed since the last calculation");
         return None;
     }
 
-    let elapsed = current_time.duration_since(last_time);
-    if elapsed.as_secs() == 0 {
+    // after a reset the counter only covers the time since the reset,
+    // which makes the resulting rate a lower bound of the actual one
+    let mut counter_reset = false;
+    let mut diff = |current: u64, last: u64| match calc_counter_delta(current, last, counter_width)
+    {
+        CounterDelta::Increase(diff) => diff,
+        CounterDelta::Reset(current) => {
+            counter_reset = true;
+            current
+        }
+    };
+
+    let rx_bps = calc_bps(
+        diff(current_counters.rx_bytes, last_counters.rx_bytes),
+        elapsed,
+    );
+    let tx_bps = calc_bps(
+        diff(current_counters.tx_bytes, last_counters.tx_bytes),
+        elapsed,
+    );
+    let mut rate = |current: u64, last: u64| diff(current, last) as f64 / elapsed.as_secs_f64();
+
+    let packet_rates = PacketRates {
+        rx_packets: rate(current_counters.rx_packets, last_counters.rx_packets),
+        tx_packets: rate(current_counters.tx_packets, last_counters.tx_packets),
+        rx_errors: rate(current_counters.rx_errors, last_counters.rx_errors),
+        tx_errors: rate(current_counters.tx_errors, last_counters.tx_errors),
+        rx_dropped: rate(current_counters.rx_dropped, last_counters.rx_dropped),
+        tx_dropped: rate(current_counters.tx_dropped, last_counters.tx_dropped),
+        rx_fifo_errors: rate(
+            current_counters.rx_fifo_errors,
+            last_counters.rx_fifo_errors,
+        ),
+        tx_fifo_errors: rate(
+            current_counters.tx_fifo_errors,
+            last_counters.tx_fifo_errors,
+        ),
+        collisions: rate(current_counters.collisions, last_counters.collisions),
+    };
+
+    if counter_reset {
         trace!(
-            "Duration since last calculation is less than a second {:?}",
-            elapsed.as_millis()
+            "Counter reset detected {:?} {:?}",
+            current_counters,
+            last_counters
         );
-        return None;
     }
 
-    let duration_in_s = elapsed.as_secs();
+    let bandwidth = Bandwidth {
+        counter_reset,
+        packet_rates,
+        tx_bps,
+        rx_bps,
+        ..Default::default()
+    };
+    trace!("Calculated bandwidth: {:?}", bandwidth);
 
-    let rx_diff = current_counter_values.rx - last_counter_values.rx;
-    let tx_diff = current_counter_values.tx - last_counter_values.tx;
+    Some((bandwidth, elapsed))
+}
 
-    let rx_bps = (rx_diff / duration_in_s) * 8;
-    let tx_bps = (tx_diff / duration_in_s) * 8;
+/// A counter smaller than its last value either wrapped around or was reset.
+/// It's considered a wrap around if that implies an increase of at most half
+/// the counter range, anything larger is way more likely a reset.
+pub(crate) fn calc_counter_delta(
+    current: u64,
+    last: u64,
+    counter_width: CounterWidth,
+) -> CounterDelta {
+    if current >= last {
+        return CounterDelta::Increase(current - last);
+    }
 
-    let bandwidth = Bandwidth { rx_bps, tx_bps };
-    trace!("Calculated bandwidth: {:?}", bandwidth);
+    let max = counter_width.max_value();
+    if last > max {
+        return CounterDelta::Reset(current);
+    }
 
-    Some(bandwidth)
+    let wrapped_diff = u128::from(max - last) + u128::from(current) + 1;
+    if wrapped_diff <= u128::from(max / 2) + 1 {
+        CounterDelta::Increase(wrapped_diff as u64)
+    } else {
+        CounterDelta::Reset(current)
+    }
+}
+
+/// Converts a byte count over the elapsed duration into bits per second,
+/// calculating in nanoseconds to keep sub-second intervals precise
+fn calc_bps(byte_diff: u64, elapsed: Duration) -> u64 {
+    let bps = u128::from(byte_diff) * 8 * 1_000_000_000 / elapsed.as_nanos();
+
+    u64::try_from(bps).unwrap_or(u64::MAX)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Mutex;
 
     use tokio::time;
 
+    use crate::test_util::{
+        is_approx_rate, FlakyCounterSource, MockCounterSource, MockRateCounterSource,
+    };
+
     #[test]
     fn test_calc_bandwidth_first_run() {
-        let current_counter_values = CounterValues { rx: 0, tx: 0 };
-        let last_counter_values = CounterValues { rx: 0, tx: 0 };
+        let current_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
         let last_time = None;
         let current_time = Instant::now();
 
         assert_eq!(
             None,
             calc_bandwidth(
-                &current_counter_values,
-                &last_counter_values,
+                &current_counters,
+                &last_counters,
                 &last_time,
                 &current_time,
+                CounterWidth::Bits64,
             )
         );
     }
 
     #[test]
     fn test_calc_bandwidth_last_greater_than_current() {
-        let current_counter_values = CounterValues { rx: 0, tx: 0 };
-        let last_counter_values = CounterValues { rx: 1000, tx: 1000 };
+        let current_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 1000,
+            tx_bytes: 1000,
+            ..Default::default()
+        };
         let last_time = Instant::now();
         let current_time = last_time + Duration::from_secs(10);
 
         assert_eq!(
-            None,
+            Some((
+                Bandwidth {
+                    counter_reset: true,
+                    ..Default::default()
+                },
+                Duration::from_secs(10),
+            )),
             calc_bandwidth(
-                &current_counter_values,
-                &last_counter_values,
+                &current_counters,
+                &last_counters,
                 &Some(last_time),
                 &current_time,
+                CounterWidth::Bits64,
             )
         );
     }
 
     #[test]
-    fn test_calc_bandwidth_duration_since_last_calculation_less_than_a_second() {
-        let current_counter_values = CounterValues { rx: 1000, tx: 1000 };
-        let last_counter_values = CounterValues { rx: 0, tx: 0 };
+    fn test_calc_bandwidth_no_elapsed_time() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 1000,
+            tx_bytes: 1000,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
         let last_time = Instant::now();
         let current_time = last_time;
 
         assert_eq!(
             None,
             calc_bandwidth(
-                &current_counter_values,
-                &last_counter_values,
+                &current_counters,
+                &last_counters,
                 &Some(last_time),
                 &current_time,
+                CounterWidth::Bits64,
             )
         );
     }
 
     #[test]
     fn test_calc_bandwidth_regular() {
-        let current_counter_values = CounterValues { rx: 2000, tx: 2000 };
-        let last_counter_values = CounterValues { rx: 0, tx: 0 };
+        let current_counters = InterfaceCounters {
+            rx_bytes: 2000,
+            tx_bytes: 2000,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
         let last_time = Instant::now();
         let current_time = last_time + Duration::from_secs(2);
 
         assert_eq!(
-            Some(Bandwidth {
-                rx_bps: 8000,
-                tx_bps: 8000
-            }),
+            Some((
+                Bandwidth {
+                    tx_bps: 8000,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_secs(2),
+            )),
             calc_bandwidth(
-                &current_counter_values,
-                &last_counter_values,
+                &current_counters,
+                &last_counters,
                 &Some(last_time),
                 &current_time,
+                CounterWidth::Bits64,
             )
         );
     }
 
-    #[tokio::test]
-    async fn test_counter_rate_bandwidth_provider() {
-        let counter_source = MockCounterSource {
-            rx: Mutex::new(vec![0, 1000].into_iter().cycle()),
-            tx: Mutex::new(vec![0, 1000].into_iter().cycle()),
+    #[test]
+    fn test_calc_bandwidth_fractional_seconds() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 2900,
+            tx_bytes: 1000,
+            ..Default::default()
         };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
+        let last_time = Instant::now();
+        let current_time = last_time + Duration::from_millis(2900);
+
+        assert_eq!(
+            Some((
+                Bandwidth {
+                    tx_bps: 2758,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_millis(2900),
+            )),
+            calc_bandwidth(
+                &current_counters,
+                &last_counters,
+                &Some(last_time),
+                &current_time,
+                CounterWidth::Bits64,
+            )
+        );
+    }
+
+    #[test]
+    fn test_calc_bandwidth_sub_second() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 250,
+            tx_bytes: 3,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            ..Default::default()
+        };
+        let last_time = Instant::now();
+        let current_time = last_time + Duration::from_millis(250);
+
+        assert_eq!(
+            Some((
+                Bandwidth {
+                    tx_bps: 96,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_millis(250),
+            )),
+            calc_bandwidth(
+                &current_counters,
+                &last_counters,
+                &Some(last_time),
+                &current_time,
+                CounterWidth::Bits64,
+            )
+        );
+    }
+
+    #[test]
+    fn test_calc_bandwidth_32bit_wrap_around() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 999,
+            tx_bytes: 2000,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: u64::from(u32::MAX) - 1000,
+            tx_bytes: 1000,
+            ..Default::default()
+        };
+        let last_time = Instant::now();
+        let current_time = last_time + Duration::from_secs(2);
+
+        assert_eq!(
+            Some((
+                Bandwidth {
+                    tx_bps: 4000,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_secs(2),
+            )),
+            calc_bandwidth(
+                &current_counters,
+                &last_counters,
+                &Some(last_time),
+                &current_time,
+                CounterWidth::Bits32,
+            )
+        );
+    }
+
+    #[test]
+    fn test_calc_bandwidth_reset_after_driver_reload() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 2000,
+            tx_bytes: 5000,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 1_000_000_000_000,
+            tx_bytes: 4000,
+            ..Default::default()
+        };
+        let last_time = Instant::now();
+        let current_time = last_time + Duration::from_secs(2);
+
+        assert_eq!(
+            Some((
+                Bandwidth {
+                    counter_reset: true,
+                    tx_bps: 4000,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_secs(2),
+            )),
+            calc_bandwidth(
+                &current_counters,
+                &last_counters,
+                &Some(last_time),
+                &current_time,
+                CounterWidth::Bits64,
+            )
+        );
+    }
+
+    #[test]
+    fn test_calc_bandwidth_packet_rates() {
+        let current_counters = InterfaceCounters {
+            rx_bytes: 3000,
+            tx_bytes: 1000,
+            rx_packets: 30,
+            tx_packets: 11,
+            rx_errors: 2,
+            rx_dropped: 5,
+            tx_fifo_errors: 1,
+            collisions: 4,
+            ..Default::default()
+        };
+        let last_counters = InterfaceCounters {
+            rx_bytes: 1000,
+            rx_packets: 10,
+            tx_packets: 10,
+            rx_dropped: 1,
+            ..Default::default()
+        };
+        let last_time = Instant::now();
+        let current_time = last_time + Duration::from_secs(2);
+
+        assert_eq!(
+            Some((
+                Bandwidth {
+                    packet_rates: PacketRates {
+                        rx_packets: 10.0,
+                        tx_packets: 0.5,
+                        rx_errors: 1.0,
+                        rx_dropped: 2.0,
+                        tx_fifo_errors: 0.5,
+                        collisions: 2.0,
+                        ..Default::default()
+                    },
+                    tx_bps: 4000,
+                    rx_bps: 8000,
+                    ..Default::default()
+                },
+                Duration::from_secs(2),
+            )),
+            calc_bandwidth(
+                &current_counters,
+                &last_counters,
+                &Some(last_time),
+                &current_time,
+                CounterWidth::Bits64,
+            )
+        );
+    }
+
+    #[test]
+    fn test_calc_counter_delta() {
+        assert_eq!(
+            CounterDelta::Increase(10),
+            calc_counter_delta(20, 10, CounterWidth::Bits32)
+        );
+        assert_eq!(
+            CounterDelta::Increase(11),
+            calc_counter_delta(5, u64::from(u32::MAX) - 5, CounterWidth::Bits32)
+        );
+        assert_eq!(
+            CounterDelta::Increase(11),
+            calc_counter_delta(5, u64::MAX - 5, CounterWidth::Bits64)
+        );
+        assert_eq!(
+            CounterDelta::Reset(5),
+            calc_counter_delta(5, 1_000_000, CounterWidth::Bits32)
+        );
+        assert_eq!(
+            CounterDelta::Reset(5),
+            calc_counter_delta(5, 1_000_000, CounterWidth::Bits64)
+        );
+        assert_eq!(
+            CounterDelta::Reset(5),
+            calc_counter_delta(5, u64::from(u32::MAX) + 1, CounterWidth::Bits32)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counter_rate_bandwidth_provider_counts_resets() {
+        let counter_source = MockCounterSource::new(vec![1000, 2000, 0, 1000], vec![0]);
 
         let bandwidth_provider =
-            CounterRateBandwidthProvider::new(counter_source, Duration::from_secs(1));
+            CounterRateBandwidthProvider::new(counter_source, Duration::from_millis(100));
 
         let loop_start = Instant::now();
-        let expected_bandwidth = Bandwidth {
-            rx_bps: 8000,
-            tx_bps: 8000,
-        };
         loop {
             time::delay_for(Duration::from_millis(50)).await;
             let bandwidth = bandwidth_provider.current_bandwidth();
 
             if loop_start.elapsed() >= Duration::from_secs(2) {
-                panic!("Failed to retrieve the expected bandwidth in time");
+                panic!("Failed to retrieve the expected counter resets in time");
             }
 
-            if *bandwidth == expected_bandwidth {
+            if bandwidth.counter_resets >= 2 {
                 break;
             }
         }
+    }
 
-        assert_eq!(true, true);
+    #[tokio::test]
+    async fn test_counter_rate_bandwidth_provider_skips_failed_reads() {
+        let counter_source = FlakyCounterSource::new(MockRateCounterSource::new(1000, 1000));
+
+        let bandwidth_provider =
+            CounterRateBandwidthProvider::new(counter_source, Duration::from_millis(100));
+        let mut updates = bandwidth_provider.get_update_channel_receiver();
+        updates.recv().await;
+
+        // the failed reads are spread over these updates
+        for _ in 0..4 {
+            updates.recv().await;
+            let bandwidth = bandwidth_provider.current_bandwidth();
+
+            assert_eq!(0, bandwidth.counter_resets);
+            assert!(is_approx_rate(8000, bandwidth.rx_bps));
+            assert!(is_approx_rate(8000, bandwidth.rx_window_max_bps));
+            assert!(is_approx_rate(8000, bandwidth.percentiles.one.rx.peak_bps));
+        }
     }
 
-    struct MockCounterSource<T: Iterator> {
-        rx: Mutex<T>,
-        tx: Mutex<T>,
+    #[test]
+    fn test_calc_bps_overflow() {
+        assert_eq!(u64::MAX, calc_bps(u64::MAX, Duration::from_nanos(1)));
     }
 
-    impl<T: Iterator + Send + Sync> CounterSource for MockCounterSource<T>
-    where
-        T: std::iter::Iterator<Item = u64>,
-    {
-        fn get_rx(&self) -> u64 {
-            self.rx.lock().unwrap().next().unwrap()
+    #[tokio::test]
+    async fn test_counter_rate_bandwidth_provider_sub_second_interval() {
+        let counter_source = MockRateCounterSource::new(4000, 2000);
+
+        let bandwidth_provider =
+            CounterRateBandwidthProvider::new(counter_source, Duration::from_millis(250));
+
+        let loop_start = Instant::now();
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let bandwidth = bandwidth_provider.current_bandwidth();
+
+            if loop_start.elapsed() >= Duration::from_secs(1) {
+                panic!("Failed to retrieve a bandwidth in time");
+            }
+
+            if bandwidth.rx_bps > 0 {
+                assert!(is_approx_rate(32000, bandwidth.rx_bps));
+                assert!(is_approx_rate(16000, bandwidth.tx_bps));
+                break;
+            }
         }
+    }
+
+    #[tokio::test]
+    async fn test_counter_rate_bandwidth_provider() {
+        let counter_source = MockRateCounterSource::new(1000, 1000);
+
+        let bandwidth_provider =
+            CounterRateBandwidthProvider::new(counter_source, Duration::from_secs(1));
+
+        let loop_start = Instant::now();
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let bandwidth = bandwidth_provider.current_bandwidth();
 
-        fn get_tx(&self) -> u64 {
-            self.tx.lock().unwrap().next().unwrap()
+            if loop_start.elapsed() >= Duration::from_secs(2) {
+                panic!("Failed to retrieve the expected bandwidth in time");
+            }
+
+            if is_approx_rate(8000, bandwidth.rx_bps) && is_approx_rate(8000, bandwidth.tx_bps) {
+                break;
+            }
         }
+
+        assert_eq!(true, true);
     }
 }
@@ -1,16 +1,136 @@
+mod netlink;
+mod proc_net_dev;
+mod snapshot;
+
+use std::convert::TryFrom;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tokio::sync::watch;
+
+pub use netlink::NetlinkLinkStats;
+pub use proc_net_dev::ProcNetDev;
+pub use snapshot::{CountersSnapshot, InterfaceCountersReader, SnapshotCounterSource};
+
+/// Width of the underlying hardware counters, determines where they wrap around.
+/// Deserialized from the number of bits, either 32 or 64.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum CounterWidth {
+    Bits32,
+    Bits64,
+}
+
+impl Default for CounterWidth {
+    fn default() -> Self {
+        CounterWidth::Bits64
+    }
+}
+
+impl CounterWidth {
+    pub fn max_value(self) -> u64 {
+        match self {
+            CounterWidth::Bits32 => u64::from(u32::MAX),
+            CounterWidth::Bits64 => u64::MAX,
+        }
+    }
+}
 
-use log::warn;
+impl TryFrom<u8> for CounterWidth {
+    type Error = String;
+
+    fn try_from(bits: u8) -> Result<Self, Self::Error> {
+        match bits {
+            32 => Ok(CounterWidth::Bits32),
+            64 => Ok(CounterWidth::Bits64),
+            _ => Err(format!("Invalid counter width {}, expected 32 or 64", bits)),
+        }
+    }
+}
+
+/// Snapshot of the statistics counters of a network interface, counters
+/// that aren't available are zero
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub rx_fifo_errors: u64,
+    pub tx_fifo_errors: u64,
+    pub collisions: u64,
+}
 
 pub trait CounterSource: Send + Sync {
-    fn get_rx(&self) -> u64;
-    fn get_tx(&self) -> u64;
+    /// Fails instead of reporting zeros, which would look like a counter reset
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters>;
+
+    /// Link capacity in bits per second
+    fn get_capacity(&self) -> Option<u64> {
+        None
+    }
+
+    fn get_counter_width(&self) -> CounterWidth {
+        CounterWidth::Bits64
+    }
+
+    /// Notifies about new counters of sources reading them on their own
+    /// schedule, their readers follow it instead of their update interval
+    fn subscribe(&self) -> Option<watch::Receiver<()>> {
+        None
+    }
+}
+
+/// Link properties shared by all counter sources of an interface
+#[derive(Debug, Default, Clone)]
+pub struct LinkOptions {
+    speed_file: Option<PathBuf>,
+    capacity: Option<u64>,
+    counter_width: CounterWidth,
+}
+
+impl LinkOptions {
+    /// Reads the link capacity from a sysfs speed file containing Mbit/s,
+    /// the capacity is unknown while the file is missing, e.g. without /sys
+    pub fn with_speed_file<T: Into<PathBuf>>(mut self, speed_file: T) -> Self {
+        self.speed_file = Some(speed_file.into());
+        self
+    }
+
+    /// Overrides the link capacity in bits per second, e.g. for virtual
+    /// interfaces or contracted commit rates
+    pub fn with_capacity(mut self, capacity: u64) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Some drivers expose 32 bit counters that wrap around way more often
+    pub fn with_counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.counter_width = counter_width;
+        self
+    }
+
+    fn capacity(&self) -> Option<u64> {
+        self.capacity.or_else(|| {
+            self.speed_file
+                .as_ref()
+                .and_then(|speed_file| read_speed(speed_file))
+        })
+    }
 }
 
 pub struct FileCounterSource {
     rx_file: PathBuf,
     tx_file: PathBuf,
+    statistics_dir: Option<PathBuf>,
+    link: LinkOptions,
 }
 
 impl FileCounterSource {
@@ -18,38 +138,120 @@ impl FileCounterSource {
         FileCounterSource {
             rx_file: rx_file.into(),
             tx_file: tx_file.into(),
+            statistics_dir: None,
+            link: Default::default(),
         }
     }
 
+    /// Reads the byte counters from `<sysfs_root>/class/net/<interface>/statistics`
+    pub fn for_interface<T: AsRef<Path>>(sysfs_root: T, interface: &str) -> Self {
+        let statistics_dir = sysfs_root
+            .as_ref()
+            .join("class/net")
+            .join(interface)
+            .join("statistics");
+
+        FileCounterSource::new(
+            statistics_dir.join("rx_bytes"),
+            statistics_dir.join("tx_bytes"),
+        )
+        .with_statistics_dir(&statistics_dir)
+        .with_link(
+            LinkOptions::default().with_speed_file(
+                sysfs_root
+                    .as_ref()
+                    .join("class/net")
+                    .join(interface)
+                    .join("speed"),
+            ),
+        )
+    }
+
+    /// Reads the packet, error and drop counters from a sysfs statistics directory
+    pub fn with_statistics_dir<T: Into<PathBuf>>(mut self, statistics_dir: T) -> Self {
+        self.statistics_dir = Some(statistics_dir.into());
+        self
+    }
+
+    pub fn with_link(mut self, link: LinkOptions) -> Self {
+        self.link = link;
+        self
+    }
+
     fn parse_number(content: &str) -> Result<u64, std::num::ParseIntError> {
         content.trim().parse()
     }
 }
 
+impl FileCounterSource {
+    fn read_counter(file: &Path) -> anyhow::Result<u64> {
+        let file_content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read counter file {}", file.to_string_lossy()))?;
+
+        Self::parse_number(&file_content).with_context(|| {
+            format!(
+                "Failed to parse counter value from file {}",
+                file.to_string_lossy()
+            )
+        })
+    }
+
+    /// Not every driver provides all statistics counters, missing ones are zero
+    fn read_optional_counter(file: &Path) -> anyhow::Result<u64> {
+        match fs::metadata(file) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            _ => Self::read_counter(file),
+        }
+    }
+}
+
 impl CounterSource for FileCounterSource {
-    fn get_rx(&self) -> u64 {
-        fs::read_to_string(&self.rx_file)
-            .map_err(anyhow::Error::new)
-            .and_then(|file_content| Self::parse_number(&file_content).map_err(anyhow::Error::new))
-            .unwrap_or_else(|e| {
-                warn!("Failed to get rx value from file: {:?}", e);
-
-                0
-            })
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters> {
+        let counters = InterfaceCounters {
+            rx_bytes: Self::read_counter(&self.rx_file)?,
+            tx_bytes: Self::read_counter(&self.tx_file)?,
+            ..Default::default()
+        };
+
+        let statistics_dir = match &self.statistics_dir {
+            Some(statistics_dir) => statistics_dir,
+            None => return Ok(counters),
+        };
+        let read = |counter: &str| Self::read_optional_counter(&statistics_dir.join(counter));
+
+        Ok(InterfaceCounters {
+            rx_packets: read("rx_packets")?,
+            tx_packets: read("tx_packets")?,
+            rx_errors: read("rx_errors")?,
+            tx_errors: read("tx_errors")?,
+            rx_dropped: read("rx_dropped")?,
+            tx_dropped: read("tx_dropped")?,
+            rx_fifo_errors: read("rx_fifo_errors")?,
+            tx_fifo_errors: read("tx_fifo_errors")?,
+            collisions: read("collisions")?,
+            ..counters
+        })
     }
 
-    fn get_tx(&self) -> u64 {
-        fs::read_to_string(&self.tx_file)
-            .map_err(anyhow::Error::new)
-            .and_then(|file_content| Self::parse_number(&file_content).map_err(anyhow::Error::new))
-            .unwrap_or_else(|e| {
-                warn!("Failed to get rx value from file: {:?}", e);
+    fn get_capacity(&self) -> Option<u64> {
+        self.link.capacity()
+    }
 
-                0
-            })
+    fn get_counter_width(&self) -> CounterWidth {
+        self.link.counter_width
     }
 }
 
+/// Reads the link capacity in bits per second from a sysfs speed file containing Mbit/s
+fn read_speed(speed_file: &Path) -> Option<u64> {
+    // virtual interfaces report -1, interfaces without link fail with EINVAL
+    fs::read_to_string(speed_file)
+        .ok()
+        .and_then(|file_content| file_content.trim().parse::<i64>().ok())
+        .filter(|speed| *speed > 0)
+        .map(|speed| speed as u64 * 1_000_000)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,6 +265,8 @@ mod tests {
 
     use rand::{thread_rng, Rng};
 
+    use crate::test_util::TestDir;
+
     struct TestFile {
         file_path: Box<Path>,
     }
@@ -107,8 +311,7 @@ mod tests {
     fn test_non_existent_files() {
         let source = FileCounterSource::new("invalid", "invalid");
 
-        assert_eq!(0, source.get_rx());
-        assert_eq!(0, source.get_tx());
+        assert!(source.get_counters().is_err());
     }
 
     #[test]
@@ -118,19 +321,21 @@ mod tests {
 
         let source = FileCounterSource::new(rx_file.file_path.as_ref(), tx_file.file_path.as_ref());
 
-        assert_eq!(23, source.get_rx());
-        assert_eq!(5, source.get_tx());
+        assert_eq!(23, source.get_counters().unwrap().rx_bytes);
+        assert_eq!(5, source.get_counters().unwrap().tx_bytes);
     }
 
     #[test]
     fn test_non_integer() {
         let rx_file = TestFile::new("foobar").unwrap();
-        let tx_file = TestFile::new("23.5").unwrap();
+        let tx_file = TestFile::new("23").unwrap();
 
         let source = FileCounterSource::new(rx_file.file_path.as_ref(), tx_file.file_path.as_ref());
+        assert!(source.get_counters().is_err());
 
-        assert_eq!(0, source.get_rx());
-        assert_eq!(0, source.get_tx());
+        fs::write(rx_file.file_path.as_ref(), "23").unwrap();
+        fs::write(tx_file.file_path.as_ref(), "23.5").unwrap();
+        assert!(source.get_counters().is_err());
     }
 
     #[test]
@@ -140,13 +345,70 @@ mod tests {
 
         let source = FileCounterSource::new(rx_file.file_path.as_ref(), tx_file.file_path.as_ref());
 
-        assert_eq!(23, source.get_rx());
-        assert_eq!(23, source.get_tx());
+        assert_eq!(23, source.get_counters().unwrap().rx_bytes);
+        assert_eq!(23, source.get_counters().unwrap().tx_bytes);
 
         fs::write(rx_file.file_path.as_ref(), "42").unwrap();
         fs::write(tx_file.file_path.as_ref(), "33").unwrap();
 
-        assert_eq!(42, source.get_rx());
-        assert_eq!(33, source.get_tx());
+        assert_eq!(42, source.get_counters().unwrap().rx_bytes);
+        assert_eq!(33, source.get_counters().unwrap().tx_bytes);
+    }
+
+    #[test]
+    fn test_for_interface_statistics() {
+        let sysfs_root = TestDir::new();
+        for (counter, value) in &[
+            ("rx_bytes", "1000"),
+            ("tx_bytes", "2000"),
+            ("rx_packets", "10"),
+            ("tx_packets", "20"),
+            ("rx_dropped", "3"),
+            ("collisions", "1"),
+        ] {
+            sysfs_root.write_file(format!("class/net/eth0/statistics/{}", counter), value);
+        }
+
+        let source = FileCounterSource::for_interface(sysfs_root.path(), "eth0");
+
+        assert_eq!(
+            InterfaceCounters {
+                rx_bytes: 1000,
+                tx_bytes: 2000,
+                rx_packets: 10,
+                tx_packets: 20,
+                rx_dropped: 3,
+                collisions: 1,
+                ..Default::default()
+            },
+            source.get_counters().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_capacity() {
+        let speed_file = TestFile::new("1000\n").unwrap();
+        let link = LinkOptions::default().with_speed_file(speed_file.file_path.as_ref());
+        let source = FileCounterSource::new("invalid", "invalid").with_link(link.clone());
+
+        assert_eq!(Some(1_000_000_000), source.get_capacity());
+
+        fs::write(speed_file.file_path.as_ref(), "-1\n").unwrap();
+        assert_eq!(None, source.get_capacity());
+
+        let source = source.with_link(link.with_capacity(250_000_000));
+        assert_eq!(Some(250_000_000), source.get_capacity());
+    }
+
+    #[test]
+    fn test_capacity_unknown() {
+        assert_eq!(
+            None,
+            FileCounterSource::new("invalid", "invalid").get_capacity()
+        );
+        assert_eq!(
+            None,
+            FileCounterSource::for_interface("invalid", "eth0").get_capacity()
+        );
     }
 }
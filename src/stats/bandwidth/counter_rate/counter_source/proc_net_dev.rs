@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+
+use super::{InterfaceCounters, InterfaceCountersReader};
+
+/// Reads the counters of all interfaces from `<proc_root>/net/dev`
+#[derive(Debug)]
+pub struct ProcNetDev {
+    path: PathBuf,
+}
+
+impl ProcNetDev {
+    pub fn new<T: AsRef<Path>>(proc_root: T) -> Self {
+        ProcNetDev {
+            path: proc_root.as_ref().join("net/dev"),
+        }
+    }
+}
+
+impl InterfaceCountersReader for ProcNetDev {
+    fn read_counters(&self) -> anyhow::Result<HashMap<String, InterfaceCounters>> {
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.to_string_lossy()))?;
+
+        parse_proc_net_dev(&content)
+    }
+}
+
+fn parse_proc_net_dev(content: &str) -> anyhow::Result<HashMap<String, InterfaceCounters>> {
+    // the first two lines are headers
+    content
+        .lines()
+        .skip(2)
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_interface_line)
+        .collect()
+}
+
+fn parse_interface_line(line: &str) -> anyhow::Result<(String, InterfaceCounters)> {
+    // older kernels don't separate the name and the first counter by whitespace
+    let mut parts = line.splitn(2, ':');
+    let (name, counters) = match (parts.next(), parts.next()) {
+        (Some(name), Some(counters)) => (name.trim(), counters),
+        _ => return Err(anyhow!("Invalid net/dev line {}", line)),
+    };
+
+    let fields = counters
+        .split_whitespace()
+        .map(|field| field.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid counters of interface {}", name))?;
+
+    if fields.len() < 16 {
+        return Err(anyhow!(
+            "Expected 16 counters for interface {}, got {}",
+            name,
+            fields.len()
+        ));
+    }
+
+    // receive: bytes packets errs drop fifo frame compressed multicast
+    // transmit: bytes packets errs drop fifo colls carrier compressed
+    Ok((
+        name.to_string(),
+        InterfaceCounters {
+            rx_bytes: fields[0],
+            rx_packets: fields[1],
+            rx_errors: fields[2],
+            rx_dropped: fields[3],
+            rx_fifo_errors: fields[4],
+            tx_bytes: fields[8],
+            tx_packets: fields[9],
+            tx_errors: fields[10],
+            tx_dropped: fields[11],
+            tx_fifo_errors: fields[12],
+            collisions: fields[13],
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    const PROC_NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 4242      42    0    0    0     0          0         0     4242      42    0    0    0     0       0          0
+  eth0: 1000      10    1    2    3     4          5         6     2000      20    7    8    9    10      11         12
+bond0:3000 30 0 0 0 0 0 0 4000 40 0 0 0 0 0 0
+";
+
+    #[test]
+    fn test_parse_proc_net_dev() {
+        let counters = parse_proc_net_dev(PROC_NET_DEV).unwrap();
+
+        assert_eq!(3, counters.len());
+        assert_eq!(
+            InterfaceCounters {
+                rx_bytes: 1000,
+                tx_bytes: 2000,
+                rx_packets: 10,
+                tx_packets: 20,
+                rx_errors: 1,
+                tx_errors: 7,
+                rx_dropped: 2,
+                tx_dropped: 8,
+                rx_fifo_errors: 3,
+                tx_fifo_errors: 9,
+                collisions: 10,
+            },
+            counters["eth0"]
+        );
+        assert_eq!(3000, counters["bond0"].rx_bytes);
+        assert_eq!(4000, counters["bond0"].tx_bytes);
+    }
+
+    #[test]
+    fn test_parse_proc_net_dev_invalid() {
+        assert!(parse_proc_net_dev("header\nheader\neth0: 1 2 3\n").is_err());
+        assert!(parse_proc_net_dev("header\nheader\neth0 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn test_read_proc_net_dev() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("net/dev", PROC_NET_DEV);
+
+        let counters = ProcNetDev::new(proc_root.path()).read_counters().unwrap();
+        assert_eq!(4242, counters["lo"].tx_bytes);
+
+        assert!(ProcNetDev::new("invalid").read_counters().is_err());
+    }
+}
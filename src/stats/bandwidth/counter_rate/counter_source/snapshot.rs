@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use log::{info, warn};
+use tokio::sync::watch;
+use tokio::{task, time};
+
+use super::{CounterSource, CounterWidth, InterfaceCounters, LinkOptions};
+
+type Snapshot = Arc<HashMap<String, InterfaceCounters>>;
+
+/// Reads the counters of all interfaces at once, the reads may block and
+/// run on the blocking thread pool
+pub trait InterfaceCountersReader: Debug + Send + Sync {
+    fn read_counters(&self) -> anyhow::Result<HashMap<String, InterfaceCounters>>;
+}
+
+/// Reads a snapshot of all interfaces on a single ticker and shares it with
+/// the counter sources of the single interfaces, which makes all of them see
+/// the same snapshot on every tick
+#[derive(Debug)]
+pub struct CountersSnapshot {
+    snapshot: RwLock<Option<Snapshot>>,
+    update_receiver: watch::Receiver<()>,
+}
+
+impl CountersSnapshot {
+    /// Reads the snapshots until the last counter source is dropped
+    pub fn start<T: InterfaceCountersReader + 'static>(
+        reader: T,
+        update_interval: Duration,
+    ) -> Arc<Self> {
+        let (tx, rx) = watch::channel(());
+
+        let counters_snapshot = Arc::new(CountersSnapshot {
+            snapshot: RwLock::new(None),
+            update_receiver: rx,
+        });
+
+        start_update_loop(
+            Arc::downgrade(&counters_snapshot),
+            tx,
+            Arc::new(reader),
+            update_interval,
+        );
+
+        counters_snapshot
+    }
+
+    fn get(&self) -> Option<Snapshot> {
+        self.snapshot.read().unwrap().as_ref().map(Arc::clone)
+    }
+
+    /// Names of the interfaces in the latest snapshot, none before the first one
+    pub fn interface_names(&self) -> Option<Vec<String>> {
+        self.get()
+            .map(|snapshot| snapshot.keys().cloned().collect())
+    }
+}
+
+fn start_update_loop<T: InterfaceCountersReader + 'static>(
+    counters_snapshot: Weak<CountersSnapshot>,
+    update_sender: watch::Sender<()>,
+    reader: Arc<T>,
+    update_interval: Duration,
+) {
+    info!("Start CountersSnapshot update loop");
+
+    tokio::spawn(async move {
+        let mut interval = time::interval(update_interval);
+
+        loop {
+            interval.tick().await;
+
+            let counters_snapshot = match counters_snapshot.upgrade() {
+                Some(counters_snapshot) => counters_snapshot,
+                None => {
+                    info!("Couldn't get a reference to the counters snapshot, ending update loop");
+                    break;
+                }
+            };
+
+            // without a snapshot the sources skip the tick, an empty one would
+            // look like a counter reset of every interface
+            let reader = Arc::clone(&reader);
+            match task::spawn_blocking(move || reader.read_counters()).await {
+                Ok(Ok(counters)) => {
+                    *counters_snapshot.snapshot.write().unwrap() = Some(Arc::new(counters));
+                    update_sender.broadcast(()).unwrap();
+                }
+                Ok(Err(e)) => warn!("Failed to read interface counters: {:?}", e),
+                Err(e) => warn!("Failed to join the interface counters read: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Serves the counters of a single interface from a shared `CountersSnapshot`
+pub struct SnapshotCounterSource {
+    snapshot: Arc<CountersSnapshot>,
+    interface: String,
+    link: LinkOptions,
+}
+
+impl SnapshotCounterSource {
+    pub fn new(snapshot: Arc<CountersSnapshot>, interface: &str) -> Self {
+        SnapshotCounterSource {
+            snapshot,
+            interface: interface.to_string(),
+            link: Default::default(),
+        }
+    }
+
+    pub fn with_link(mut self, link: LinkOptions) -> Self {
+        self.link = link;
+        self
+    }
+}
+
+impl CounterSource for SnapshotCounterSource {
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters> {
+        let snapshot = self
+            .snapshot
+            .get()
+            .ok_or_else(|| anyhow::anyhow!("No interface counters snapshot read yet"))?;
+
+        snapshot.get(&self.interface).cloned().ok_or_else(|| {
+            anyhow::anyhow!("Interface {} not found in counter snapshot", self.interface)
+        })
+    }
+
+    fn get_capacity(&self) -> Option<u64> {
+        self.link.capacity()
+    }
+
+    fn get_counter_width(&self) -> CounterWidth {
+        self.link.counter_width
+    }
+
+    fn subscribe(&self) -> Option<watch::Receiver<()>> {
+        // the initial value of the channel only ticks once there's a snapshot,
+        // before it the first update is the first snapshot
+        let mut updates = self.snapshot.update_receiver.clone();
+        updates.recv().now_or_never();
+        if self.snapshot.get().is_some() {
+            updates = self.snapshot.update_receiver.clone();
+        }
+
+        Some(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Debug, Default)]
+    struct CountingReader {
+        reads: Arc<AtomicU64>,
+        /// Fails every other read
+        flaky: bool,
+    }
+
+    impl InterfaceCountersReader for CountingReader {
+        fn read_counters(&self) -> anyhow::Result<HashMap<String, InterfaceCounters>> {
+            let reads = self.reads.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.flaky && reads % 2 == 0 {
+                anyhow::bail!("Mock read failure");
+            }
+
+            let counters = InterfaceCounters {
+                rx_bytes: reads,
+                ..Default::default()
+            };
+
+            Ok(vec![
+                ("eth0".into(), counters.clone()),
+                ("bond0".into(), counters),
+            ]
+            .into_iter()
+            .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_snapshot() {
+        let reader = CountingReader::default();
+        let reads = Arc::clone(&reader.reads);
+
+        let snapshot = CountersSnapshot::start(reader, Duration::from_millis(100));
+        let eth0 = SnapshotCounterSource::new(Arc::clone(&snapshot), "eth0");
+        let bond0 = SnapshotCounterSource::new(Arc::clone(&snapshot), "bond0");
+        let eth1 = SnapshotCounterSource::new(Arc::clone(&snapshot), "eth1");
+        let mut updates = eth0.subscribe().unwrap();
+
+        // subscribed before the first snapshot, which is the first update
+        updates.recv().await.unwrap();
+
+        assert_eq!(1, eth0.get_counters().unwrap().rx_bytes);
+        assert_eq!(1, bond0.get_counters().unwrap().rx_bytes);
+        assert!(eth1.get_counters().is_err());
+        assert_eq!(1, reads.load(Ordering::SeqCst));
+        let mut names = snapshot.interface_names().unwrap();
+        names.sort();
+        assert_eq!(vec!["bond0", "eth0"], names);
+
+        updates.recv().await.unwrap();
+        assert_eq!(2, bond0.get_counters().unwrap().rx_bytes);
+        assert_eq!(2, eth0.get_counters().unwrap().rx_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_after_first_snapshot() {
+        let snapshot = CountersSnapshot::start(CountingReader::default(), Duration::from_secs(1));
+        let eth0 = SnapshotCounterSource::new(Arc::clone(&snapshot), "eth0");
+        eth0.subscribe().unwrap().recv().await.unwrap();
+
+        // the latest snapshot is received right away
+        let bond0 = SnapshotCounterSource::new(Arc::clone(&snapshot), "bond0");
+        bond0.subscribe().unwrap().recv().await.unwrap();
+        assert_eq!(1, bond0.get_counters().unwrap().rx_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_failed_read_keeps_snapshot() {
+        let reader = CountingReader {
+            flaky: true,
+            ..Default::default()
+        };
+        let reads = Arc::clone(&reader.reads);
+
+        let snapshot = CountersSnapshot::start(reader, Duration::from_millis(50));
+        let eth0 = SnapshotCounterSource::new(Arc::clone(&snapshot), "eth0");
+        let mut updates = eth0.subscribe().unwrap();
+
+        updates.recv().await.unwrap();
+        assert_eq!(1, eth0.get_counters().unwrap().rx_bytes);
+
+        // the failed second read isn't published
+        updates.recv().await.unwrap();
+        assert_eq!(3, reads.load(Ordering::SeqCst));
+        assert_eq!(3, eth0.get_counters().unwrap().rx_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_update_loop_ends_without_sources() {
+        let snapshot =
+            CountersSnapshot::start(CountingReader::default(), Duration::from_millis(10));
+        let mut updates = SnapshotCounterSource::new(Arc::clone(&snapshot), "eth0")
+            .subscribe()
+            .unwrap();
+        drop(snapshot);
+
+        while updates.recv().await.is_some() {}
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::super::{BandwidthPercentiles, RatePercentiles, WindowPercentiles};
+
+const ONE_MINUTE: Duration = Duration::from_secs(60);
+const FIVE_MINUTES: Duration = Duration::from_secs(5 * 60);
+const FIFTEEN_MINUTES: Duration = Duration::from_secs(15 * 60);
+
+/// Keeps the rate samples for the percentiles of the last 15 minutes and
+/// the sliding window of the smoother, whichever is longer
+#[derive(Debug)]
+pub struct RateHistory {
+    retention: Duration,
+    samples: VecDeque<RateSample>,
+}
+
+#[derive(Debug)]
+pub struct RateSample {
+    pub time: Instant,
+    /// Time span the rates were measured over
+    pub elapsed: Duration,
+    pub tx_bps: u64,
+    pub rx_bps: u64,
+}
+
+impl Default for RateHistory {
+    fn default() -> Self {
+        RateHistory::new(FIFTEEN_MINUTES)
+    }
+}
+
+impl RateHistory {
+    pub fn new(retention: Duration) -> Self {
+        RateHistory {
+            retention: retention.max(FIFTEEN_MINUTES),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Evicts the samples older than the retention, the newest sample is always kept
+    pub fn push(&mut self, sample: RateSample) {
+        let time = sample.time;
+        self.samples.push_back(sample);
+
+        while self.samples.len() > 1 && time.duration_since(self.samples[0].time) >= self.retention
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&RateSample> {
+        self.samples.back()
+    }
+
+    /// Samples taken less than `length` before the newest one, newest first
+    pub fn window(&self, length: Duration) -> impl Iterator<Item = &RateSample> {
+        let mut samples = self.samples.iter().rev();
+        let newest = samples.next();
+        let newest_time = newest.map(|sample| sample.time);
+
+        newest.into_iter().chain(samples.take_while(move |sample| {
+            newest_time.map_or(false, |newest_time| {
+                newest_time.duration_since(sample.time) < length
+            })
+        }))
+    }
+
+    pub fn percentiles(&self) -> BandwidthPercentiles {
+        let mut rates = Vec::with_capacity(self.samples.len());
+
+        BandwidthPercentiles {
+            one: self.window_percentiles(ONE_MINUTE, &mut rates),
+            five: self.window_percentiles(FIVE_MINUTES, &mut rates),
+            fifteen: self.window_percentiles(FIFTEEN_MINUTES, &mut rates),
+        }
+    }
+
+    /// Reuses the scratch buffer of the rates for all windows and directions
+    fn window_percentiles(&self, length: Duration, rates: &mut Vec<u64>) -> WindowPercentiles {
+        rates.clear();
+        rates.extend(self.window(length).map(|sample| sample.tx_bps));
+        let tx = rate_percentiles(rates);
+
+        rates.clear();
+        rates.extend(self.window(length).map(|sample| sample.rx_bps));
+        let rx = rate_percentiles(rates);
+
+        WindowPercentiles { tx, rx }
+    }
+}
+
+/// Picks the nearest-rank percentiles, as used for 95th percentile billing
+fn rate_percentiles(rates: &mut [u64]) -> RatePercentiles {
+    let len = rates.len();
+    if len == 0 {
+        return RatePercentiles::default();
+    }
+
+    rates.sort_unstable();
+
+    RatePercentiles {
+        p50_bps: rates[nearest_rank_index(len, 50)],
+        p95_bps: rates[nearest_rank_index(len, 95)],
+        p99_bps: rates[nearest_rank_index(len, 99)],
+        peak_bps: rates[len - 1],
+    }
+}
+
+/// Index of the nearest-rank percentile within `len` sorted rates
+fn nearest_rank_index(len: usize, percentile: usize) -> usize {
+    ((percentile * len + 99) / 100).max(1) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::seq::SliceRandom;
+    use rand::thread_rng;
+
+    fn push(history: &mut RateHistory, time: Instant, tx_bps: u64, rx_bps: u64) {
+        history.push(RateSample {
+            time,
+            elapsed: Duration::from_secs(1),
+            tx_bps,
+            rx_bps,
+        });
+    }
+
+    #[test]
+    fn test_rate_percentiles() {
+        let mut rates: Vec<u64> = (1..=100).collect();
+        rates.shuffle(&mut thread_rng());
+
+        assert_eq!(
+            RatePercentiles {
+                p50_bps: 50,
+                p95_bps: 95,
+                p99_bps: 99,
+                peak_bps: 100,
+            },
+            rate_percentiles(&mut rates)
+        );
+        assert_eq!(
+            RatePercentiles {
+                p50_bps: 7,
+                p95_bps: 7,
+                p99_bps: 7,
+                peak_bps: 7,
+            },
+            rate_percentiles(&mut [7])
+        );
+        assert_eq!(RatePercentiles::default(), rate_percentiles(&mut []));
+    }
+
+    #[test]
+    fn test_rate_history_windows() {
+        let start = Instant::now();
+        let mut history = RateHistory::default();
+
+        // one sample per second, the old samples carry higher rates
+        for secs in 0..20 * 60 {
+            let tx_bps = if secs < 14 * 60 { 10_000 } else { secs };
+            push(
+                &mut history,
+                start + Duration::from_secs(secs),
+                tx_bps,
+                1000,
+            );
+        }
+
+        let percentiles = history.percentiles();
+
+        assert_eq!(15 * 60, history.samples.len());
+        assert_eq!(1199, percentiles.one.tx.peak_bps);
+        assert_eq!(1169, percentiles.one.tx.p50_bps);
+        assert_eq!(1196, percentiles.one.tx.p95_bps);
+        assert_eq!(1199, percentiles.five.tx.peak_bps);
+        assert_eq!(1049, percentiles.five.tx.p50_bps);
+        assert_eq!(10_000, percentiles.fifteen.tx.peak_bps);
+        assert_eq!(10_000, percentiles.fifteen.tx.p50_bps);
+        assert_eq!(1000, percentiles.fifteen.rx.p99_bps);
+    }
+
+    #[test]
+    fn test_rate_history_retention() {
+        let start = Instant::now();
+        let mut history = RateHistory::new(Duration::from_secs(20 * 60));
+
+        for secs in 0..30 * 60 {
+            push(&mut history, start + Duration::from_secs(secs), secs, 0);
+        }
+
+        assert_eq!(20 * 60, history.samples.len());
+        assert_eq!(3, history.window(Duration::from_secs(3)).count());
+        assert_eq!(1, history.window(Duration::from_secs(0)).count());
+        assert_eq!(
+            Some(30 * 60 - 1),
+            history
+                .window(Duration::from_secs(3))
+                .map(|s| s.tx_bps)
+                .next()
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use super::super::Bandwidth;
+use super::percentiles::{RateHistory, RateSample};
+
+/// Defaults to a 10s half life and a 60s window, which the settings build on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    /// Age at which a sample only carries half of its weight in the moving average
+    pub ewma_half_life: Duration,
+    /// Length of the sliding window the mean and max rates are calculated over
+    pub window: Duration,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            ewma_half_life: Duration::from_secs(10),
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Adds the smoothed views to the instantaneous bandwidth samples of a single counter source
+#[derive(Debug)]
+pub struct BandwidthSmoother {
+    smoothing: Smoothing,
+    tx_ewma: Ewma,
+    rx_ewma: Ewma,
+}
+
+impl BandwidthSmoother {
+    pub fn new(smoothing: Smoothing) -> Self {
+        BandwidthSmoother {
+            smoothing,
+            tx_ewma: Default::default(),
+            rx_ewma: Default::default(),
+        }
+    }
+
+    /// `elapsed` is the time span the sample's rates were measured over, the
+    /// sliding window is taken from the history that already holds the sample
+    pub fn smooth(
+        &mut self,
+        elapsed: Duration,
+        bandwidth: Bandwidth,
+        history: &RateHistory,
+    ) -> Bandwidth {
+        let half_life = self.smoothing.ewma_half_life;
+        let length = self.smoothing.window;
+        let window = || history.window(length);
+
+        Bandwidth {
+            tx_ewma_bps: self.tx_ewma.update(bandwidth.tx_bps, elapsed, half_life),
+            rx_ewma_bps: self.rx_ewma.update(bandwidth.rx_bps, elapsed, half_life),
+            tx_window_mean_bps: window_mean(window(), |sample| sample.tx_bps),
+            rx_window_mean_bps: window_mean(window(), |sample| sample.rx_bps),
+            tx_window_max_bps: window().map(|sample| sample.tx_bps).max().unwrap_or(0),
+            rx_window_max_bps: window().map(|sample| sample.rx_bps).max().unwrap_or(0),
+            ..bandwidth
+        }
+    }
+}
+
+/// Exponentially weighted moving average, weighting samples by the time
+/// they cover to cope with irregular update intervals
+#[derive(Debug, Default)]
+struct Ewma {
+    value: Option<f64>,
+}
+
+impl Ewma {
+    fn update(&mut self, sample: u64, elapsed: Duration, half_life: Duration) -> u64 {
+        let sample = sample as f64;
+        let value = match self.value {
+            None => sample,
+            Some(value) => {
+                let decay = 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64());
+
+                sample + (value - sample) * decay
+            }
+        };
+
+        self.value = Some(value);
+
+        value.round() as u64
+    }
+}
+
+/// Time weighted mean of the rates within the window
+fn window_mean<'a, I, F>(samples: I, rate: F) -> u64
+where
+    I: Iterator<Item = &'a RateSample>,
+    F: Fn(&RateSample) -> u64,
+{
+    let (weighted_sum, total_secs) =
+        samples.fold((0.0, 0.0), |(weighted_sum, total_secs), sample| {
+            let secs = sample.elapsed.as_secs_f64();
+
+            (weighted_sum + rate(sample) as f64 * secs, total_secs + secs)
+        });
+
+    if total_secs > 0.0 {
+        (weighted_sum / total_secs).round() as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    #[test]
+    fn test_ewma_half_life() {
+        let half_life = Duration::from_secs(10);
+        let mut ewma = Ewma::default();
+
+        assert_eq!(1000, ewma.update(1000, Duration::from_secs(1), half_life));
+        assert_eq!(500, ewma.update(0, Duration::from_secs(10), half_life));
+        assert_eq!(250, ewma.update(0, Duration::from_secs(10), half_life));
+        assert_eq!(625, ewma.update(1000, Duration::from_secs(10), half_life));
+    }
+
+    fn sample(start: Instant, secs: u64, elapsed: u64, tx_bps: u64, rx_bps: u64) -> RateSample {
+        RateSample {
+            time: start + Duration::from_secs(secs),
+            elapsed: Duration::from_secs(elapsed),
+            tx_bps,
+            rx_bps,
+        }
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let start = Instant::now();
+        let length = Duration::from_secs(10);
+        let mut history = RateHistory::default();
+
+        history.push(sample(start, 0, 1, 9000, 0));
+        history.push(sample(start, 3, 3, 1000, 0));
+        history.push(sample(start, 4, 1, 5000, 0));
+
+        assert_eq!(3, history.window(length).count());
+        assert_eq!(3400, window_mean(history.window(length), |s| s.tx_bps));
+
+        history.push(sample(start, 10, 6, 2000, 0));
+
+        assert_eq!(3, history.window(length).count());
+        assert_eq!(2000, window_mean(history.window(length), |s| s.tx_bps));
+        assert_eq!(Some(5000), history.window(length).map(|s| s.tx_bps).max());
+        assert_eq!(0, window_mean(history.window(length), |s| s.rx_bps));
+    }
+
+    #[test]
+    fn test_bandwidth_smoother() {
+        let start = Instant::now();
+        let mut history = RateHistory::default();
+        let mut smoother = BandwidthSmoother::new(Smoothing {
+            ewma_half_life: Duration::from_secs(1),
+            window: Duration::from_secs(5),
+        });
+        let mut smooth = |secs: u64, tx_bps: u64, rx_bps: u64| {
+            history.push(sample(start, secs, 1, tx_bps, rx_bps));
+            smoother.smooth(
+                Duration::from_secs(1),
+                Bandwidth {
+                    tx_bps,
+                    rx_bps,
+                    ..Default::default()
+                },
+                &history,
+            )
+        };
+
+        smooth(0, 4000, 8000);
+        let bandwidth = smooth(1, 0, 4000);
+
+        assert_eq!(0, bandwidth.tx_bps);
+        assert_eq!(2000, bandwidth.tx_ewma_bps);
+        assert_eq!(6000, bandwidth.rx_ewma_bps);
+        assert_eq!(2000, bandwidth.tx_window_mean_bps);
+        assert_eq!(6000, bandwidth.rx_window_mean_bps);
+        assert_eq!(4000, bandwidth.tx_window_max_bps);
+        assert_eq!(8000, bandwidth.rx_window_max_bps);
+
+        // the samples left the window but are still kept for the percentiles
+        let bandwidth = smooth(10, 1000, 1000);
+        assert_eq!(1000, bandwidth.tx_window_max_bps);
+        assert_eq!(1000, bandwidth.rx_window_mean_bps);
+    }
+}
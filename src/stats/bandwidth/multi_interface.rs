@@ -0,0 +1,599 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{info, trace, warn};
+use regex::Regex;
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    Bandwidth, BandwidthProvider, CounterRateBandwidthProvider, CounterWidth, CountersSnapshot,
+    FileCounterSource, InterfaceBandwidth, LinkOptions, RateHistory, RateSample, Smoothing,
+    SnapshotCounterSource,
+};
+use crate::stats::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct MultiInterfaceBandwidth {
+    pub total: Arc<Bandwidth>,
+    pub interfaces: Arc<InterfaceBandwidth>,
+}
+
+/// Periodically rescans the interfaces for ones matching the pattern, either
+/// in `<sysfs_root>/class/net` or in the latest counters snapshot if given
+#[derive(Debug, Clone)]
+pub struct InterfaceDiscovery {
+    pub sysfs_root: PathBuf,
+    pub pattern: Regex,
+    pub rescan_interval: Duration,
+    pub update_interval: Duration,
+    pub counter_width: CounterWidth,
+    pub smoothing: Smoothing,
+    /// Serves and discovers the interfaces from shared snapshots instead of
+    /// their sysfs files, only the speed files are still read from sysfs
+    pub counters_snapshot: Option<Arc<CountersSnapshot>>,
+}
+
+/// Aggregates the bandwidth of one counter rate pipeline per network interface
+pub struct MultiInterfaceBandwidthProvider {
+    shared: Arc<Shared>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+struct Shared {
+    interfaces: RwLock<BTreeMap<String, CounterRateBandwidthProvider>>,
+    bandwidth: RwLock<Arc<MultiInterfaceBandwidth>>,
+    /// Total rates for the percentiles, which can't be summed up per interface
+    total_history: Mutex<TotalHistory>,
+    update_sender: watch::Sender<UpdateNotification>,
+}
+
+impl MultiInterfaceBandwidthProvider {
+    pub fn new(interfaces: Vec<(String, CounterRateBandwidthProvider)>) -> Self {
+        let (tx, rx) = watch::channel(None);
+
+        let shared = Arc::new(Shared {
+            interfaces: RwLock::new(BTreeMap::new()),
+            bandwidth: RwLock::new(Arc::new(Default::default())),
+            total_history: Mutex::new(TotalHistory::default()),
+            update_sender: tx,
+        });
+
+        for (name, interface) in interfaces {
+            add_interface(&shared, name, interface);
+        }
+
+        Self {
+            shared,
+            update_receiver: rx,
+        }
+    }
+
+    /// Tracks the given interfaces and additionally all discovered ones,
+    /// discovered interfaces are dropped as soon as they vanish
+    pub fn with_discovery(
+        interfaces: Vec<(String, CounterRateBandwidthProvider)>,
+        discovery: InterfaceDiscovery,
+    ) -> Self {
+        let provider = Self::new(interfaces);
+
+        start_discovery_loop(Arc::downgrade(&provider.shared), discovery);
+
+        provider
+    }
+
+    pub fn current_multi_interface_bandwidth(&self) -> Arc<MultiInterfaceBandwidth> {
+        Arc::clone(&self.shared.bandwidth.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for MultiInterfaceBandwidthProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        let bandwidth = self.current_multi_interface_bandwidth();
+
+        node_stats.bandwidth = Arc::clone(&bandwidth.total);
+        node_stats.interface_bandwidth = Arc::clone(&bandwidth.interfaces);
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for MultiInterfaceBandwidthProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for MultiInterfaceBandwidthProvider {
+    fn get_name(&self) -> &'static str {
+        "MultiInterfaceBandwidthProvider"
+    }
+}
+
+fn add_interface(shared: &Arc<Shared>, name: String, interface: CounterRateBandwidthProvider) {
+    start_forward_loop(
+        Arc::downgrade(shared),
+        name.clone(),
+        interface.get_update_channel_receiver(),
+    );
+
+    shared.interfaces.write().unwrap().insert(name, interface);
+}
+
+/// Total rates sampled once per update tick of the interfaces
+#[derive(Default)]
+struct TotalHistory {
+    rates: RateHistory,
+    /// Interfaces with measured updates since the last sample
+    updated: HashSet<String>,
+}
+
+impl TotalHistory {
+    /// Records a measured update of the interface, returns whether it
+    /// completes the tick. That's the case once all interfaces updated, or
+    /// when the interface updates again before the others, e.g. while they
+    /// fail to read their counters.
+    fn record_update<'a>(
+        &mut self,
+        interface_name: &str,
+        interface_names: impl IntoIterator<Item = &'a String>,
+    ) -> bool {
+        let lagging = self.updated.contains(interface_name);
+        if lagging {
+            self.updated.clear();
+        }
+        self.updated.insert(interface_name.to_string());
+
+        let complete = interface_names
+            .into_iter()
+            .all(|name| self.updated.contains(name));
+        if complete {
+            self.updated.clear();
+        }
+
+        lagging || complete
+    }
+}
+
+/// Aggregates the interfaces again, passing on the name and read time of the
+/// measured interface update that caused it, if any
+fn update_bandwidth(shared: &Shared, update: Option<(&str, SystemTime)>) {
+    let mut bandwidth = shared.bandwidth.write().unwrap();
+    let interfaces = shared.interfaces.read().unwrap();
+    let mut total_history = shared.total_history.lock().unwrap();
+
+    let tick_completed = match update {
+        Some((interface_name, _)) => total_history.record_update(interface_name, interfaces.keys()),
+        None => false,
+    };
+    let sample_time = if tick_completed {
+        Some(Instant::now())
+    } else {
+        None
+    };
+    *bandwidth = Arc::new(aggregate_bandwidth(
+        &interfaces,
+        &mut total_history.rates,
+        sample_time,
+    ));
+    shared
+        .update_sender
+        .broadcast(update.map(|(_, read_at)| read_at))
+        .unwrap();
+}
+
+fn start_forward_loop(
+    shared: Weak<Shared>,
+    interface_name: String,
+    mut update_receiver: watch::Receiver<UpdateNotification>,
+) {
+    info!(
+        "Start MultiInterfaceBandwidthProvider forward loop for interface {}",
+        interface_name
+    );
+
+    tokio::spawn(async move {
+        while let Some(read_at) = update_receiver.recv().await {
+            let shared = match shared.upgrade() {
+                Some(shared) => shared,
+                None => break,
+            };
+
+            trace!("Received bandwidth update of interface {}", interface_name);
+            update_bandwidth(
+                &shared,
+                read_at.map(|read_at| (interface_name.as_str(), read_at)),
+            );
+        }
+
+        info!(
+            "Ending MultiInterfaceBandwidthProvider forward loop for interface {}",
+            interface_name
+        );
+    });
+}
+
+fn start_discovery_loop(shared: Weak<Shared>, discovery: InterfaceDiscovery) {
+    info!(
+        "Start MultiInterfaceBandwidthProvider discovery loop with pattern {}",
+        discovery.pattern
+    );
+
+    tokio::spawn(async move { discovery_loop(shared, discovery).await });
+}
+
+async fn discovery_loop(shared: Weak<Shared>, discovery: InterfaceDiscovery) {
+    let mut interval = time::interval(discovery.rescan_interval);
+    let mut discovered: HashSet<String> = HashSet::new();
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => {
+                info!("Couldn't get a reference to the interface storage, ending discovery loop");
+                break;
+            }
+        };
+
+        let scan = match &discovery.counters_snapshot {
+            Some(counters_snapshot) => Ok(counters_snapshot
+                .interface_names()
+                .map(|names| matching_interfaces(names, &discovery.pattern))),
+            None => scan_interfaces(&discovery.sysfs_root, &discovery.pattern).map(Some),
+        };
+
+        match scan {
+            Ok(Some(current)) => {
+                if apply_scan(&shared, &mut discovered, current, &discovery) {
+                    update_bandwidth(&shared, None);
+                }
+            }
+            Ok(None) => trace!("No interface counters snapshot read yet, skipping the scan"),
+            Err(e) => warn!("Failed to scan network interfaces: {:?}", e),
+        }
+
+        interval.tick().await;
+    }
+}
+
+/// Starts tracking new and stops tracking vanished interfaces,
+/// returns whether the set of tracked interfaces changed
+fn apply_scan(
+    shared: &Arc<Shared>,
+    discovered: &mut HashSet<String>,
+    current: HashSet<String>,
+    discovery: &InterfaceDiscovery,
+) -> bool {
+    let vanished: Vec<String> = discovered.difference(&current).cloned().collect();
+    for name in vanished.iter() {
+        info!("Network interface {} vanished, stop tracking", name);
+        shared.interfaces.write().unwrap().remove(name);
+        discovered.remove(name);
+    }
+
+    let mut added = false;
+    for name in current {
+        if discovered.contains(&name) || shared.interfaces.read().unwrap().contains_key(&name) {
+            continue;
+        }
+
+        info!("Discovered network interface {}, start tracking", name);
+        let interface = discovered_interface(discovery, &name);
+        add_interface(shared, name.clone(), interface);
+        discovered.insert(name);
+        added = true;
+    }
+
+    added || !vanished.is_empty()
+}
+
+fn discovered_interface(
+    discovery: &InterfaceDiscovery,
+    name: &str,
+) -> CounterRateBandwidthProvider {
+    let link = LinkOptions::default()
+        .with_speed_file(
+            discovery
+                .sysfs_root
+                .join("class/net")
+                .join(name)
+                .join("speed"),
+        )
+        .with_counter_width(discovery.counter_width);
+
+    match &discovery.counters_snapshot {
+        Some(counters_snapshot) => CounterRateBandwidthProvider::with_smoothing(
+            SnapshotCounterSource::new(Arc::clone(counters_snapshot), name).with_link(link),
+            discovery.update_interval,
+            discovery.smoothing,
+        ),
+        None => CounterRateBandwidthProvider::with_smoothing(
+            FileCounterSource::for_interface(&discovery.sysfs_root, name).with_link(link),
+            discovery.update_interval,
+            discovery.smoothing,
+        ),
+    }
+}
+
+fn scan_interfaces(sysfs_root: &Path, pattern: &Regex) -> anyhow::Result<HashSet<String>> {
+    let mut names = vec![];
+
+    for entry in fs::read_dir(sysfs_root.join("class/net"))? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+
+    Ok(matching_interfaces(names, pattern))
+}
+
+fn matching_interfaces(names: Vec<String>, pattern: &Regex) -> HashSet<String> {
+    names
+        .into_iter()
+        .filter(|name| pattern.is_match(name))
+        .collect()
+}
+
+/// Sums up the interfaces, the total is added to the history if a sample time
+/// is given
+fn aggregate_bandwidth(
+    interfaces: &BTreeMap<String, CounterRateBandwidthProvider>,
+    total_history: &mut RateHistory,
+    sample_time: Option<Instant>,
+) -> MultiInterfaceBandwidth {
+    let interfaces: InterfaceBandwidth = interfaces
+        .iter()
+        .map(|(name, interface)| (name.clone(), interface.current_bandwidth()))
+        .collect();
+
+    let total = interfaces
+        .values()
+        .fold(Bandwidth::default(), |acc, bandwidth| Bandwidth {
+            counter_reset: acc.counter_reset || bandwidth.counter_reset,
+            counter_resets: acc.counter_resets + bandwidth.counter_resets,
+            tx_ewma_bps: acc.tx_ewma_bps + bandwidth.tx_ewma_bps,
+            rx_ewma_bps: acc.rx_ewma_bps + bandwidth.rx_ewma_bps,
+            tx_window_mean_bps: acc.tx_window_mean_bps + bandwidth.tx_window_mean_bps,
+            rx_window_mean_bps: acc.rx_window_mean_bps + bandwidth.rx_window_mean_bps,
+            // the interface peaks don't necessarily coincide, which makes
+            // their sum an upper bound of the total peak
+            tx_window_max_bps: acc.tx_window_max_bps + bandwidth.tx_window_max_bps,
+            rx_window_max_bps: acc.rx_window_max_bps + bandwidth.rx_window_max_bps,
+            packet_rates: &acc.packet_rates + &bandwidth.packet_rates,
+            tx_bps: acc.tx_bps + bandwidth.tx_bps,
+            rx_bps: acc.rx_bps + bandwidth.rx_bps,
+            ..Default::default()
+        });
+
+    // the total capacity is only meaningful if it is known for every interface
+    let total_capacity = interfaces
+        .values()
+        .map(|bandwidth| bandwidth.capacity_bps)
+        .sum::<Option<u64>>();
+    if let Some(now) = sample_time {
+        let elapsed = total_history
+            .latest()
+            .map_or(Duration::from_secs(0), |latest| {
+                now.duration_since(latest.time)
+            });
+        total_history.push(RateSample {
+            time: now,
+            elapsed,
+            tx_bps: total.tx_bps,
+            rx_bps: total.rx_bps,
+        });
+    }
+    let total = Bandwidth {
+        percentiles: total_history.percentiles(),
+        ..total
+    }
+    .with_capacity(total_capacity);
+
+    MultiInterfaceBandwidth {
+        total: Arc::new(total),
+        interfaces: Arc::new(interfaces),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::time::Instant;
+
+    use std::collections::HashMap;
+
+    use crate::stats::bandwidth::{InterfaceCounters, InterfaceCountersReader};
+    use crate::test_util::MockRateCounterSource;
+    use crate::test_util::{is_approx_rate, TestDir};
+
+    #[tokio::test]
+    async fn test_multi_interface_bandwidth_provider() {
+        let bandwidth_provider = MultiInterfaceBandwidthProvider::new(vec![
+            (
+                "eth0".to_string(),
+                CounterRateBandwidthProvider::new(
+                    MockRateCounterSource::new(1000, 2000),
+                    Duration::from_secs(1),
+                ),
+            ),
+            (
+                "eth1".to_string(),
+                CounterRateBandwidthProvider::new(
+                    MockRateCounterSource::new(3000, 4000),
+                    Duration::from_secs(1),
+                ),
+            ),
+        ]);
+
+        let loop_start = Instant::now();
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let bandwidth = bandwidth_provider.current_multi_interface_bandwidth();
+
+            if loop_start.elapsed() >= Duration::from_secs(2) {
+                panic!("Failed to retrieve the expected bandwidth in time");
+            }
+
+            let eth0 = &bandwidth.interfaces["eth0"];
+            let eth1 = &bandwidth.interfaces["eth1"];
+            if eth0.rx_bps > 0 && eth1.rx_bps > 0 {
+                assert!(is_approx_rate(16000, eth0.tx_bps));
+                assert!(is_approx_rate(8000, eth0.rx_bps));
+                assert!(is_approx_rate(32000, eth1.tx_bps));
+                assert!(is_approx_rate(24000, eth1.rx_bps));
+                assert_eq!(eth0.tx_bps + eth1.tx_bps, bandwidth.total.tx_bps);
+                assert_eq!(eth0.rx_bps + eth1.rx_bps, bandwidth.total.rx_bps);
+                assert_eq!(
+                    eth0.rx_ewma_bps + eth1.rx_ewma_bps,
+                    bandwidth.total.rx_ewma_bps
+                );
+                // taken from the history of the totals, not summed up
+                let percentiles = &bandwidth.total.percentiles.one;
+                assert_eq!(bandwidth.total.tx_bps, percentiles.tx.peak_bps);
+                assert_eq!(bandwidth.total.rx_bps, percentiles.rx.peak_bps);
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_total_sampled_once_per_tick() {
+        let names = vec!["eth0".to_string(), "eth1".to_string()];
+        let mut total_history = TotalHistory::default();
+
+        assert!(!total_history.record_update("eth0", &names));
+        assert!(total_history.record_update("eth1", &names));
+        assert!(!total_history.record_update("eth1", &names));
+        assert!(total_history.record_update("eth0", &names));
+
+        // eth1 fails to read, eth0 still completes a tick on every update
+        assert!(!total_history.record_update("eth0", &names));
+        assert!(total_history.record_update("eth0", &names));
+        assert!(total_history.record_update("eth0", &names));
+    }
+
+    #[test]
+    fn test_scan_interfaces() {
+        let sysfs_root = TestDir::new();
+        for name in &["eth0", "eth1", "bond0", "bond0.100", "lo"] {
+            sysfs_root.write_file(format!("class/net/{}/statistics/rx_bytes", name), "0");
+        }
+
+        let interfaces = scan_interfaces(
+            sysfs_root.path(),
+            &Regex::new(r"^(eth\d+|bond\d+)$").unwrap(),
+        )
+        .unwrap();
+
+        let expected: HashSet<String> = vec!["eth0", "eth1", "bond0"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(expected, interfaces);
+    }
+
+    #[tokio::test]
+    async fn test_interface_discovery() {
+        let sysfs_root = TestDir::new();
+        sysfs_root.write_file("class/net/eth0/statistics/rx_bytes", "0");
+        sysfs_root.write_file("class/net/eth0/statistics/tx_bytes", "0");
+        sysfs_root.write_file("class/net/lo/statistics/rx_bytes", "0");
+
+        let bandwidth_provider = MultiInterfaceBandwidthProvider::with_discovery(
+            vec![],
+            InterfaceDiscovery {
+                sysfs_root: sysfs_root.path().to_path_buf(),
+                pattern: Regex::new("^eth").unwrap(),
+                rescan_interval: Duration::from_millis(100),
+                update_interval: Duration::from_secs(1),
+                counter_width: CounterWidth::Bits64,
+                smoothing: Default::default(),
+                counters_snapshot: None,
+            },
+        );
+
+        wait_for_interfaces(&bandwidth_provider, &["eth0"]).await;
+
+        sysfs_root.write_file("class/net/eth1/statistics/rx_bytes", "0");
+        wait_for_interfaces(&bandwidth_provider, &["eth0", "eth1"]).await;
+
+        fs::remove_dir_all(sysfs_root.path().join("class/net/eth0")).unwrap();
+        wait_for_interfaces(&bandwidth_provider, &["eth1"]).await;
+    }
+
+    /// Reads the interfaces listed in the shared names
+    #[derive(Debug)]
+    struct NamesReader(Arc<Mutex<Vec<&'static str>>>);
+
+    impl InterfaceCountersReader for NamesReader {
+        fn read_counters(&self) -> anyhow::Result<HashMap<String, InterfaceCounters>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|name| (name.to_string(), Default::default()))
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interface_discovery_from_snapshot() {
+        let names = Arc::new(Mutex::new(vec!["eth0", "lo"]));
+        let counters_snapshot =
+            CountersSnapshot::start(NamesReader(Arc::clone(&names)), Duration::from_millis(50));
+
+        // without any sysfs files
+        let bandwidth_provider = MultiInterfaceBandwidthProvider::with_discovery(
+            vec![],
+            InterfaceDiscovery {
+                sysfs_root: PathBuf::from("missing"),
+                pattern: Regex::new("^eth").unwrap(),
+                rescan_interval: Duration::from_millis(100),
+                update_interval: Duration::from_secs(1),
+                counter_width: CounterWidth::Bits64,
+                smoothing: Default::default(),
+                counters_snapshot: Some(counters_snapshot),
+            },
+        );
+
+        wait_for_interfaces(&bandwidth_provider, &["eth0"]).await;
+
+        *names.lock().unwrap() = vec!["eth1", "lo"];
+        wait_for_interfaces(&bandwidth_provider, &["eth1"]).await;
+
+        let bandwidth = bandwidth_provider.current_multi_interface_bandwidth();
+        assert_eq!(None, bandwidth.interfaces["eth1"].capacity_bps);
+    }
+
+    async fn wait_for_interfaces(
+        bandwidth_provider: &MultiInterfaceBandwidthProvider,
+        expected: &[&str],
+    ) {
+        let loop_start = Instant::now();
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let bandwidth = bandwidth_provider.current_multi_interface_bandwidth();
+
+            if loop_start.elapsed() >= Duration::from_secs(2) {
+                panic!("Failed to discover the expected interfaces in time");
+            }
+
+            if bandwidth
+                .interfaces
+                .keys()
+                .map(String::as_str)
+                .eq(expected.iter().copied())
+            {
+                break;
+            }
+        }
+    }
+}
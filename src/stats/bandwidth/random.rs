@@ -1,5 +1,5 @@
 use std::sync::{Arc, RwLock, Weak};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use log::info;
 use rand::thread_rng;
@@ -8,18 +8,18 @@ use tokio::sync::watch;
 use tokio::time;
 
 use super::{Bandwidth, BandwidthProvider};
-use crate::stats::{NodeStatsDataSource, NodeStatsUpdateNotifier};
+use crate::stats::{NodeStatsDataSource, NodeStatsUpdateNotifier, UpdateNotification};
 
 pub struct RandomBandwidthProvider {
     bandwidth: Arc<RwLock<Arc<Bandwidth>>>,
-    update_receiver: watch::Receiver<()>,
+    update_receiver: watch::Receiver<UpdateNotification>,
 }
 
 impl RandomBandwidthProvider {
     pub fn new() -> Self {
         let shared_bandwidth = Arc::new(RwLock::new(Arc::new(Default::default())));
 
-        let (tx, rx) = watch::channel(());
+        let (tx, rx) = watch::channel(None);
 
         let provider = Self {
             bandwidth: Arc::clone(&shared_bandwidth),
@@ -39,7 +39,7 @@ impl BandwidthProvider for RandomBandwidthProvider {
 }
 
 impl NodeStatsUpdateNotifier for RandomBandwidthProvider {
-    fn get_update_channel_receiver(&self) -> watch::Receiver<()> {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
         self.update_receiver.clone()
     }
 }
@@ -56,13 +56,19 @@ impl Default for RandomBandwidthProvider {
     }
 }
 
-fn start_update_loop(bandwidth: Weak<RwLock<Arc<Bandwidth>>>, update_sender: watch::Sender<()>) {
+fn start_update_loop(
+    bandwidth: Weak<RwLock<Arc<Bandwidth>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+) {
     info!("Start RandomBandwidthProvider update loop");
 
     tokio::spawn(async move { update_loop(bandwidth, update_sender).await });
 }
 
-async fn update_loop(bandwidth: Weak<RwLock<Arc<Bandwidth>>>, update_sender: watch::Sender<()>) {
+async fn update_loop(
+    bandwidth: Weak<RwLock<Arc<Bandwidth>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+) {
     let mut interval = time::interval(Duration::from_secs(5));
 
     loop {
@@ -79,8 +85,12 @@ async fn update_loop(bandwidth: Weak<RwLock<Arc<Bandwidth>>>, update_sender: wat
             let tx_bps = thread_rng().gen::<u64>();
 
             info!("Updating");
-            *bandwidth.write().unwrap() = Arc::new(Bandwidth { rx_bps, tx_bps });
-            update_sender.broadcast(()).unwrap();
+            *bandwidth.write().unwrap() = Arc::new(Bandwidth {
+                tx_bps,
+                rx_bps,
+                ..Default::default()
+            });
+            update_sender.broadcast(Some(SystemTime::now())).unwrap();
         }
 
         interval.tick().await;
@@ -0,0 +1,545 @@
+mod cpu_stat;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Context};
+use log::{info, trace, warn};
+use regex::Regex;
+use tokio::sync::watch;
+use tokio::time;
+
+use super::bandwidth::{InterfaceCountersReader, ProcNetDev};
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use cpu_stat::{read_cpu_stat, CpuStat};
+
+/// Resource usage of a single cgroup v2
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CgroupStats {
+    /// Path of the cgroup relative to the cgroup root
+    pub name: String,
+    /// CPU time per second, 1.0 equals one fully used core
+    pub cpu_usage: f64,
+    pub cpu_user: f64,
+    pub cpu_system: f64,
+    pub memory_current: u64,
+    /// Absent if the cgroup has no process to find the network namespace by
+    /// or shares the host's network namespace
+    pub network: Option<CgroupNetwork>,
+}
+
+/// Bandwidth of all interfaces except loopback in the cgroup's network namespace
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CgroupNetwork {
+    pub rx_bps: u64,
+    pub tx_bps: u64,
+}
+
+pub struct CgroupStatsProvider {
+    cgroup_stats: Arc<RwLock<Arc<Vec<CgroupStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+/// Which cgroups are tracked and where their statistics are read from
+#[derive(Debug, Clone)]
+pub struct CgroupSelection {
+    pub cgroup_root: PathBuf,
+    pub proc_root: PathBuf,
+    /// Matched against the path relative to the cgroup root
+    pub pattern: Regex,
+}
+
+impl CgroupStatsProvider {
+    pub fn new(selection: CgroupSelection, update_interval: Duration) -> Self {
+        let shared_cgroup_stats = Arc::new(RwLock::new(Arc::new(Default::default())));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            cgroup_stats: Arc::clone(&shared_cgroup_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_cgroup_stats),
+            tx,
+            selection,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_cgroup_stats(&self) -> Arc<Vec<CgroupStats>> {
+        Arc::clone(&self.cgroup_stats.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for CgroupStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.cgroups = Some(self.current_cgroup_stats());
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for CgroupStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for CgroupStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "CgroupStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    cgroup_stats: Weak<RwLock<Arc<Vec<CgroupStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    selection: CgroupSelection,
+    update_interval: Duration,
+) {
+    info!("Start CgroupStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(cgroup_stats, update_sender, selection, update_interval).await
+    });
+}
+
+async fn update_loop(
+    cgroup_stats: Weak<RwLock<Arc<Vec<CgroupStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    selection: CgroupSelection,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+    let mut last_samples = HashMap::new();
+    let host_netns = host_netns(&selection.proc_root);
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let cgroup_stats = match cgroup_stats.upgrade() {
+            Some(cgroup_stats) => cgroup_stats,
+            None => {
+                info!("Couldn't get a reference to the cgroup stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match find_cgroups(&selection.cgroup_root, &selection.pattern) {
+            Ok(names) => {
+                let samples = read_samples(&selection, names, host_netns);
+                let new_cgroup_stats = calc_cgroup_stats(&samples, &last_samples);
+                trace!("Calculated cgroup stats: {:?}", new_cgroup_stats);
+
+                *cgroup_stats.write().unwrap() = Arc::new(new_cgroup_stats);
+                update_sender.broadcast(Some(SystemTime::now())).unwrap();
+                last_samples = samples;
+            }
+            Err(e) => {
+                warn!("Failed to find cgroups: {:?}", e);
+
+                *cgroup_stats.write().unwrap() = Default::default();
+                update_sender.broadcast(None).unwrap();
+                last_samples.clear();
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+#[derive(Debug)]
+struct CgroupSample {
+    time: Instant,
+    cpu: CpuStat,
+    memory_current: u64,
+    network: Option<NetworkSample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NetworkSample {
+    /// Inode of the network namespace the counters were read from
+    netns: u64,
+    /// Summed byte counters of all interfaces except loopback
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+fn read_samples(
+    selection: &CgroupSelection,
+    names: Vec<String>,
+    host_netns: Option<u64>,
+) -> HashMap<String, CgroupSample> {
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let cgroup_dir = selection.cgroup_root.join(&name);
+
+            match read_sample(&cgroup_dir, &selection.proc_root, host_netns) {
+                Ok(sample) => Some((name, sample)),
+                Err(e) => {
+                    // cgroups may vanish between finding and reading them
+                    warn!("Failed to read cgroup {}: {:?}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn read_sample(
+    cgroup_dir: &Path,
+    proc_root: &Path,
+    host_netns: Option<u64>,
+) -> anyhow::Result<CgroupSample> {
+    let memory_current = cgroup_dir.join("memory.current");
+    let memory_current = fs::read_to_string(&memory_current)
+        .with_context(|| format!("Failed to read {}", memory_current.to_string_lossy()))?
+        .trim()
+        .parse()?;
+
+    Ok(CgroupSample {
+        time: Instant::now(),
+        cpu: read_cpu_stat(cgroup_dir)?,
+        memory_current,
+        network: read_network_counters(cgroup_dir, proc_root, host_netns).unwrap_or_else(|e| {
+            trace!(
+                "No network counters for cgroup {}: {:?}",
+                cgroup_dir.to_string_lossy(),
+                e
+            );
+            None
+        }),
+    })
+}
+
+/// Reads the counters of the network namespace of the first process in the cgroup,
+/// none for the host's namespace whose traffic isn't specific to the cgroup
+fn read_network_counters(
+    cgroup_dir: &Path,
+    proc_root: &Path,
+    host_netns: Option<u64>,
+) -> anyhow::Result<Option<NetworkSample>> {
+    let procs = fs::read_to_string(cgroup_dir.join("cgroup.procs"))?;
+    let pid = match procs.lines().next() {
+        Some(pid) => pid.trim(),
+        None => return Ok(None),
+    };
+
+    let netns = read_netns(&proc_root.join(pid))?;
+    if Some(netns) == host_netns {
+        return Ok(None);
+    }
+
+    let counters = ProcNetDev::new(proc_root.join(pid)).read_counters()?;
+    let (rx_bytes, tx_bytes) = counters
+        .iter()
+        .filter(|(name, _)| name.as_str() != "lo")
+        .fold((0, 0), |(rx, tx), (_, counters)| {
+            (rx + counters.rx_bytes, tx + counters.tx_bytes)
+        });
+
+    Ok(Some(NetworkSample {
+        netns,
+        rx_bytes,
+        tx_bytes,
+    }))
+}
+
+/// The network namespace of the init process
+fn host_netns(proc_root: &Path) -> Option<u64> {
+    read_netns(&proc_root.join("1"))
+        .map_err(|e| warn!("Failed to read the host network namespace: {:?}", e))
+        .ok()
+}
+
+/// Reads the inode of a process' network namespace from its `ns/net` link,
+/// which points to e.g. `net:[4026531992]`
+fn read_netns(proc_dir: &Path) -> anyhow::Result<u64> {
+    let link = proc_dir.join("ns/net");
+    let target = fs::read_link(&link)
+        .with_context(|| format!("Failed to read {}", link.to_string_lossy()))?;
+    let target = target.to_string_lossy();
+
+    target
+        .strip_prefix("net:[")
+        .and_then(|inode| inode.strip_suffix(']'))
+        .ok_or_else(|| anyhow!("Unexpected network namespace {}", target))?
+        .parse()
+        .with_context(|| format!("Invalid network namespace {}", target))
+}
+
+/// Returns the paths relative to the cgroup root of all cgroups matching the pattern
+fn find_cgroups(cgroup_root: &Path, pattern: &Regex) -> anyhow::Result<Vec<String>> {
+    if !cgroup_root.is_dir() {
+        return Err(anyhow!(
+            "cgroup root {} is not a directory",
+            cgroup_root.to_string_lossy()
+        ));
+    }
+
+    let mut cgroups = vec![];
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = pending.pop() {
+        let entries = match fs::read_dir(cgroup_root.join(&relative_dir)) {
+            Ok(entries) => entries,
+            Err(e) => {
+                trace!("Failed to read cgroup dir {:?}: {:?}", relative_dir, e);
+                continue;
+            }
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+
+            let relative_path = relative_dir.join(entry.file_name());
+            let name = relative_path.to_string_lossy().into_owned();
+
+            // the stats of a cgroup already cover its children
+            if pattern.is_match(&name) {
+                cgroups.push(name);
+            } else {
+                pending.push(relative_path);
+            }
+        }
+    }
+
+    cgroups.sort();
+
+    Ok(cgroups)
+}
+
+/// Cgroups seen for the first time are left out until there is a previous
+/// sample to calculate their rates with
+fn calc_cgroup_stats(
+    samples: &HashMap<String, CgroupSample>,
+    last_samples: &HashMap<String, CgroupSample>,
+) -> Vec<CgroupStats> {
+    let mut cgroup_stats: Vec<CgroupStats> = samples
+        .iter()
+        .filter_map(|(name, sample)| {
+            let last_sample = last_samples.get(name)?;
+            let elapsed = sample.time.duration_since(last_sample.time);
+            if elapsed.as_nanos() == 0 {
+                return None;
+            }
+
+            let elapsed_usec = elapsed.as_secs_f64() * 1_000_000.0;
+            let cpu_rate =
+                |current: u64, last: u64| current.saturating_sub(last) as f64 / elapsed_usec;
+            let bps = |current: u64, last: u64| {
+                (current.saturating_sub(last) as f64 * 8.0 / elapsed.as_secs_f64()) as u64
+            };
+
+            let network = match (sample.network, last_sample.network) {
+                // a process in another namespace may be the first one now,
+                // and interfaces may be recreated within the same namespace
+                (Some(network), Some(last_network))
+                    if network.netns == last_network.netns
+                        && network.rx_bytes >= last_network.rx_bytes
+                        && network.tx_bytes >= last_network.tx_bytes =>
+                {
+                    Some(CgroupNetwork {
+                        rx_bps: bps(network.rx_bytes, last_network.rx_bytes),
+                        tx_bps: bps(network.tx_bytes, last_network.tx_bytes),
+                    })
+                }
+                _ => None,
+            };
+
+            Some(CgroupStats {
+                name: name.clone(),
+                cpu_usage: cpu_rate(sample.cpu.usage_usec, last_sample.cpu.usage_usec),
+                cpu_user: cpu_rate(sample.cpu.user_usec, last_sample.cpu.user_usec),
+                cpu_system: cpu_rate(sample.cpu.system_usec, last_sample.cpu.system_usec),
+                memory_current: sample.memory_current,
+                network,
+            })
+        })
+        .collect();
+
+    cgroup_stats.sort_by(|a, b| a.name.cmp(&b.name));
+
+    cgroup_stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 4242      42    0    0    0     0          0         0     4242      42    0    0    0     0       0          0
+  eth0: 1000      10    0    0    0     0          0         0     2000      20    0    0    0     0       0          0
+  eth1:  500       5    0    0    0     0          0         0      500       5    0    0    0     0       0          0
+";
+
+    fn write_netns(proc_root: &TestDir, pid: &str, netns: &str) {
+        let ns_dir = proc_root.path().join(pid).join("ns");
+        fs::create_dir_all(&ns_dir).unwrap();
+        std::os::unix::fs::symlink(netns, ns_dir.join("net")).unwrap();
+    }
+
+    fn write_cgroup(root: &TestDir, name: &str, pid: Option<&str>) {
+        root.write_file(
+            format!("{}/cpu.stat", name),
+            "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\n",
+        );
+        root.write_file(format!("{}/memory.current", name), "4096\n");
+        root.write_file(format!("{}/cgroup.procs", name), pid.unwrap_or(""));
+    }
+
+    #[test]
+    fn test_find_cgroups() {
+        let cgroup_root = TestDir::new();
+        write_cgroup(&cgroup_root, "system.slice/docker-abc.scope", None);
+        write_cgroup(&cgroup_root, "system.slice/docker-def.scope", None);
+        // covered by its matching parent
+        write_cgroup(
+            &cgroup_root,
+            "system.slice/docker-def.scope/init.scope",
+            None,
+        );
+        write_cgroup(&cgroup_root, "system.slice/sshd.service", None);
+        write_cgroup(&cgroup_root, "user.slice", None);
+
+        let pattern = Regex::new(r"^system\.slice/docker-.*\.scope$").unwrap();
+
+        assert_eq!(
+            vec![
+                "system.slice/docker-abc.scope".to_string(),
+                "system.slice/docker-def.scope".to_string(),
+            ],
+            find_cgroups(cgroup_root.path(), &pattern).unwrap()
+        );
+        assert!(find_cgroups(Path::new("invalid"), &pattern).is_err());
+    }
+
+    #[test]
+    fn test_read_sample() {
+        let cgroup_root = TestDir::new();
+        let proc_root = TestDir::new();
+        write_cgroup(&cgroup_root, "cache", Some("4242\n4243\n"));
+        write_cgroup(&cgroup_root, "host", Some("4300\n"));
+        write_cgroup(&cgroup_root, "idle", None);
+        write_netns(&proc_root, "1", "net:[4026531992]");
+        write_netns(&proc_root, "4242", "net:[4026532000]");
+        write_netns(&proc_root, "4300", "net:[4026531992]");
+        proc_root.write_file("4242/net/dev", NET_DEV);
+        proc_root.write_file("4300/net/dev", NET_DEV);
+
+        let host_netns = host_netns(proc_root.path());
+        assert_eq!(Some(4026531992), host_netns);
+        let read = |name: &str| {
+            read_sample(&cgroup_root.path().join(name), proc_root.path(), host_netns).unwrap()
+        };
+
+        let sample = read("cache");
+        assert_eq!(4096, sample.memory_current);
+        assert_eq!(3000, sample.cpu.usage_usec);
+        assert_eq!(
+            Some(NetworkSample {
+                netns: 4026532000,
+                rx_bytes: 1500,
+                tx_bytes: 2500,
+            }),
+            sample.network
+        );
+
+        assert_eq!(None, read("host").network);
+        assert_eq!(None, read("idle").network);
+    }
+
+    #[test]
+    fn test_calc_cgroup_stats() {
+        let last_time = Instant::now();
+        let network = |netns: u64, rx_bytes: u64, tx_bytes: u64| {
+            Some(NetworkSample {
+                netns,
+                rx_bytes,
+                tx_bytes,
+            })
+        };
+        let sample =
+            |time: Instant, usage_usec: u64, network: Option<NetworkSample>| CgroupSample {
+                time,
+                cpu: CpuStat {
+                    usage_usec,
+                    user_usec: usage_usec / 2,
+                    system_usec: usage_usec / 4,
+                },
+                memory_current: 1024,
+                network,
+            };
+
+        let last_samples: HashMap<String, CgroupSample> = vec![
+            (
+                "a".to_string(),
+                sample(last_time, 1_000_000, network(1, 0, 0)),
+            ),
+            ("b".to_string(), sample(last_time, 0, network(1, 5000, 0))),
+            ("d".to_string(), sample(last_time, 0, network(1, 0, 0))),
+        ]
+        .into_iter()
+        .collect();
+
+        let current_time = last_time + Duration::from_secs(2);
+        let samples: HashMap<String, CgroupSample> = vec![
+            (
+                "a".to_string(),
+                sample(current_time, 4_000_000, network(1, 1000, 2000)),
+            ),
+            ("b".to_string(), sample(current_time, 0, network(1, 0, 0))),
+            ("c".to_string(), sample(current_time, 0, None)),
+            // moved to another namespace with higher counters
+            (
+                "d".to_string(),
+                sample(current_time, 0, network(2, 9000, 9000)),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            vec![
+                CgroupStats {
+                    name: "a".into(),
+                    cpu_usage: 1.5,
+                    cpu_user: 0.75,
+                    cpu_system: 0.375,
+                    memory_current: 1024,
+                    network: Some(CgroupNetwork {
+                        rx_bps: 4000,
+                        tx_bps: 8000,
+                    }),
+                },
+                CgroupStats {
+                    name: "b".into(),
+                    memory_current: 1024,
+                    ..Default::default()
+                },
+                CgroupStats {
+                    name: "d".into(),
+                    memory_current: 1024,
+                    ..Default::default()
+                },
+            ],
+            calc_cgroup_stats(&samples, &last_samples)
+        );
+    }
+}
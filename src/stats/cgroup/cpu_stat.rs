@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+/// CPU time consumed by the processes of a cgroup in microseconds
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CpuStat {
+    pub usage_usec: u64,
+    pub user_usec: u64,
+    pub system_usec: u64,
+}
+
+pub fn read_cpu_stat(cgroup_dir: &Path) -> anyhow::Result<CpuStat> {
+    let path = cgroup_dir.join("cpu.stat");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+    parse_cpu_stat(&content)
+}
+
+fn parse_cpu_stat(content: &str) -> anyhow::Result<CpuStat> {
+    let mut usage_usec = None;
+    let mut user_usec = None;
+    let mut system_usec = None;
+
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+
+        let field = match fields.next() {
+            Some("usage_usec") => &mut usage_usec,
+            Some("user_usec") => &mut user_usec,
+            Some("system_usec") => &mut system_usec,
+            _ => continue,
+        };
+
+        *field = Some(
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing cpu.stat value in line {}", line))?
+                .parse()?,
+        );
+    }
+
+    match (usage_usec, user_usec, system_usec) {
+        (Some(usage_usec), Some(user_usec), Some(system_usec)) => Ok(CpuStat {
+            usage_usec,
+            user_usec,
+            system_usec,
+        }),
+        _ => Err(anyhow!("Incomplete cpu.stat")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        assert_eq!(
+            CpuStat {
+                usage_usec: 3000,
+                user_usec: 2000,
+                system_usec: 1000,
+            },
+            parse_cpu_stat(
+                "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\nnr_periods 0\nnr_throttled 0\n"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_cpu_stat_invalid() {
+        assert!(parse_cpu_stat("usage_usec 3000\nuser_usec 2000\n").is_err());
+        assert!(parse_cpu_stat("usage_usec foo\nuser_usec 2000\nsystem_usec 1\n").is_err());
+        assert!(parse_cpu_stat("usage_usec\n").is_err());
+    }
+}
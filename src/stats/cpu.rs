@@ -0,0 +1,337 @@
+mod proc_stat;
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use proc_stat::{read_proc_stat, CpuTimes, ProcStat};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CpuStats {
+    pub total: CpuUsage,
+    pub cores: Vec<CpuUsage>,
+}
+
+/// Cpu time shares in percent of the elapsed time
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CpuUsage {
+    pub user: f64,
+    pub system: f64,
+    pub iowait: f64,
+    pub steal: f64,
+}
+
+pub struct CpuStatsProvider {
+    cpu_stats: Arc<RwLock<Option<Arc<CpuStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl CpuStatsProvider {
+    pub fn new<T: Into<PathBuf>>(proc_root: T, update_interval: Duration) -> Self {
+        let shared_cpu_stats = Arc::new(RwLock::new(None));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            cpu_stats: Arc::clone(&shared_cpu_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_cpu_stats),
+            tx,
+            proc_root.into(),
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_cpu_stats(&self) -> Option<Arc<CpuStats>> {
+        self.cpu_stats.read().unwrap().clone()
+    }
+}
+
+impl NodeStatsUpdater for CpuStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.cpu = self.current_cpu_stats();
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for CpuStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for CpuStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "CpuStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    cpu_stats: Weak<RwLock<Option<Arc<CpuStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    info!("Start CpuStatsProvider update loop");
+
+    tokio::spawn(
+        async move { update_loop(cpu_stats, update_sender, proc_root, update_interval).await },
+    );
+}
+
+async fn update_loop(
+    cpu_stats: Weak<RwLock<Option<Arc<CpuStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+    let mut last_proc_stat: Option<ProcStat> = None;
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let cpu_stats = match cpu_stats.upgrade() {
+            Some(cpu_stats) => cpu_stats,
+            None => {
+                info!("Couldn't get a reference to the cpu stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match read_proc_stat(&proc_root) {
+            Ok(current_proc_stat) => {
+                if let Some(new_cpu_stats) = calc_cpu_stats(&current_proc_stat, &last_proc_stat) {
+                    *cpu_stats.write().unwrap() = Some(Arc::new(new_cpu_stats));
+                    update_sender.broadcast(Some(SystemTime::now())).unwrap();
+                }
+
+                last_proc_stat = Some(current_proc_stat);
+            }
+            Err(e) => {
+                warn!("Failed to read cpu times: {:?}", e);
+
+                *cpu_stats.write().unwrap() = None;
+                update_sender.broadcast(None).unwrap();
+                last_proc_stat = None;
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+fn calc_cpu_stats(current: &ProcStat, last: &Option<ProcStat>) -> Option<CpuStats> {
+    let last = last.as_ref()?;
+
+    if current.cores.len() != last.cores.len() {
+        trace!(
+            "Number of cpu cores changed from {} to {}",
+            last.cores.len(),
+            current.cores.len()
+        );
+        return None;
+    }
+
+    let cpu_stats = CpuStats {
+        total: calc_cpu_usage(&current.total, &last.total)?,
+        cores: current
+            .cores
+            .iter()
+            .zip(last.cores.iter())
+            .map(|(current, last)| calc_cpu_usage(current, last))
+            .collect::<Option<Vec<_>>>()?,
+    };
+    trace!("Calculated cpu stats: {:?}", cpu_stats);
+
+    Some(cpu_stats)
+}
+
+fn calc_cpu_usage(current: &CpuTimes, last: &CpuTimes) -> Option<CpuUsage> {
+    if current.total() < last.total() {
+        trace!(
+            "Current cpu times are smaller than the last cpu times {:?} {:?}",
+            current,
+            last
+        );
+        return None;
+    }
+
+    let elapsed = current.total() - last.total();
+    if elapsed == 0 {
+        return Some(Default::default());
+    }
+
+    let share =
+        |current: u64, last: u64| current.saturating_sub(last) as f64 * 100.0 / elapsed as f64;
+
+    Some(CpuUsage {
+        user: share(current.user + current.nice, last.user + last.nice),
+        system: share(
+            current.system + current.irq + current.softirq,
+            last.system + last.irq + last.softirq,
+        ),
+        iowait: share(current.iowait, last.iowait),
+        steal: share(current.steal, last.steal),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::test_util::TestDir;
+
+    fn cpu_times(user: u64, system: u64, idle: u64, iowait: u64, steal: u64) -> CpuTimes {
+        CpuTimes {
+            user,
+            system,
+            idle,
+            iowait,
+            steal,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_calc_cpu_stats_first_run() {
+        let current = ProcStat {
+            total: cpu_times(10, 10, 10, 10, 10),
+            cores: vec![],
+        };
+
+        assert_eq!(None, calc_cpu_stats(&current, &None));
+    }
+
+    #[test]
+    fn test_calc_cpu_stats_regular() {
+        let last = ProcStat {
+            total: cpu_times(100, 100, 100, 100, 100),
+            cores: vec![cpu_times(50, 50, 50, 50, 50), cpu_times(50, 50, 50, 50, 50)],
+        };
+        let current = ProcStat {
+            total: cpu_times(150, 120, 200, 110, 120),
+            cores: vec![
+                cpu_times(100, 60, 50, 50, 50),
+                cpu_times(50, 60, 150, 60, 70),
+            ],
+        };
+
+        assert_eq!(
+            Some(CpuStats {
+                total: CpuUsage {
+                    user: 25.0,
+                    system: 10.0,
+                    iowait: 5.0,
+                    steal: 10.0,
+                },
+                cores: vec![
+                    CpuUsage {
+                        user: 50.0 * 100.0 / 60.0,
+                        system: 10.0 * 100.0 / 60.0,
+                        iowait: 0.0,
+                        steal: 0.0,
+                    },
+                    CpuUsage {
+                        user: 0.0,
+                        system: 10.0 * 100.0 / 140.0,
+                        iowait: 10.0 * 100.0 / 140.0,
+                        steal: 20.0 * 100.0 / 140.0,
+                    },
+                ],
+            }),
+            calc_cpu_stats(&current, &Some(last))
+        );
+    }
+
+    #[test]
+    fn test_calc_cpu_stats_core_count_changed() {
+        let last = ProcStat {
+            total: cpu_times(100, 100, 100, 100, 100),
+            cores: vec![cpu_times(50, 50, 50, 50, 50)],
+        };
+        let current = ProcStat {
+            total: cpu_times(150, 120, 200, 110, 120),
+            cores: vec![
+                cpu_times(100, 60, 50, 50, 50),
+                cpu_times(50, 60, 150, 60, 70),
+            ],
+        };
+
+        assert_eq!(None, calc_cpu_stats(&current, &Some(last)));
+    }
+
+    #[test]
+    fn test_calc_cpu_stats_last_greater_than_current() {
+        let last = ProcStat {
+            total: cpu_times(100, 100, 100, 100, 100),
+            cores: vec![],
+        };
+        let current = ProcStat {
+            total: cpu_times(10, 10, 10, 10, 10),
+            cores: vec![],
+        };
+
+        assert_eq!(None, calc_cpu_stats(&current, &Some(last)));
+    }
+
+    #[test]
+    fn test_calc_cpu_stats_no_elapsed_time() {
+        let last = ProcStat {
+            total: cpu_times(100, 100, 100, 100, 100),
+            cores: vec![],
+        };
+
+        assert_eq!(
+            Some(Default::default()),
+            calc_cpu_stats(&last.clone(), &Some(last))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cpu_stats_provider() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("stat", "cpu 100 0 100 100 100 0 0 100\n");
+
+        let cpu_stats_provider =
+            CpuStatsProvider::new(proc_root.path(), Duration::from_millis(100));
+
+        time::delay_for(Duration::from_millis(50)).await;
+        proc_root.write_file("stat", "cpu 150 0 110 120 100 0 0 120\n");
+
+        let loop_start = Instant::now();
+        let expected_total = CpuUsage {
+            user: 50.0,
+            system: 10.0,
+            iowait: 0.0,
+            steal: 20.0,
+        };
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let cpu_stats = cpu_stats_provider.current_cpu_stats();
+
+            if loop_start.elapsed() >= Duration::from_secs(2) {
+                panic!("Failed to retrieve the expected cpu stats in time");
+            }
+
+            if cpu_stats.map_or(false, |cpu_stats| cpu_stats.total == expected_total) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTimes {
+    /// Sum of all accounted jiffies, guest time is already included in user and nice
+    pub fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProcStat {
+    pub total: CpuTimes,
+    pub cores: Vec<CpuTimes>,
+}
+
+pub fn read_proc_stat(proc_root: &Path) -> anyhow::Result<ProcStat> {
+    let path = proc_root.join("stat");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+    parse_proc_stat(&content)
+}
+
+fn parse_proc_stat(content: &str) -> anyhow::Result<ProcStat> {
+    let mut total = None;
+    let mut cores = vec![];
+
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+
+        match fields.next() {
+            Some("cpu") => total = Some(parse_cpu_times(fields)?),
+            Some(label) if label.starts_with("cpu") => cores.push(parse_cpu_times(fields)?),
+            _ => {}
+        }
+    }
+
+    Ok(ProcStat {
+        total: total.ok_or_else(|| anyhow!("Missing aggregate cpu line"))?,
+        cores,
+    })
+}
+
+fn parse_cpu_times<'a, T: Iterator<Item = &'a str>>(fields: T) -> anyhow::Result<CpuTimes> {
+    let values = fields
+        .map(|field| field.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if values.len() < 4 {
+        return Err(anyhow!(
+            "Expected at least 4 cpu time fields, got {}",
+            values.len()
+        ));
+    }
+
+    let value = |idx: usize| values.get(idx).copied().unwrap_or(0);
+
+    Ok(CpuTimes {
+        user: value(0),
+        nice: value(1),
+        system: value(2),
+        idle: value(3),
+        iowait: value(4),
+        irq: value(5),
+        softirq: value(6),
+        steal: value(7),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    const PROC_STAT: &str = "cpu  100 10 50 800 20 5 5 10 0 0
+cpu0 60 5 25 390 10 3 2 5 0 0
+cpu1 40 5 25 410 10 2 3 5 0 0
+intr 1234 0 0 0
+ctxt 5678
+btime 1600000000
+processes 42
+procs_running 1
+procs_blocked 0
+";
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let proc_stat = parse_proc_stat(PROC_STAT).unwrap();
+
+        assert_eq!(
+            CpuTimes {
+                user: 100,
+                nice: 10,
+                system: 50,
+                idle: 800,
+                iowait: 20,
+                irq: 5,
+                softirq: 5,
+                steal: 10,
+            },
+            proc_stat.total
+        );
+        assert_eq!(2, proc_stat.cores.len());
+        assert_eq!(60, proc_stat.cores[0].user);
+        assert_eq!(410, proc_stat.cores[1].idle);
+        assert_eq!(1000, proc_stat.total.total());
+    }
+
+    #[test]
+    fn test_parse_proc_stat_old_kernel() {
+        let proc_stat = parse_proc_stat("cpu 1 2 3 4\ncpu0 1 2 3 4\n").unwrap();
+
+        assert_eq!(4, proc_stat.total.idle);
+        assert_eq!(0, proc_stat.total.steal);
+    }
+
+    #[test]
+    fn test_parse_proc_stat_missing_aggregate() {
+        assert!(parse_proc_stat("cpu0 1 2 3 4\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_proc_stat_invalid_number() {
+        assert!(parse_proc_stat("cpu 1 2 foo 4\n").is_err());
+    }
+
+    #[test]
+    fn test_read_proc_stat_from_proc_root() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("stat", PROC_STAT);
+
+        let proc_stat = read_proc_stat(proc_root.path()).unwrap();
+
+        assert_eq!(2, proc_stat.cores.len());
+    }
+
+    #[test]
+    fn test_read_proc_stat_non_existent() {
+        assert!(read_proc_stat(Path::new("invalid")).is_err());
+    }
+}
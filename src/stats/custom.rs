@@ -0,0 +1,310 @@
+mod parser;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Context};
+use futures_util::future;
+use log::{info, trace, warn};
+use serde::Deserialize;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::{fs, time};
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+
+pub use parser::MetricParser;
+
+/// Values of the custom metrics by name
+pub type CustomMetrics = BTreeMap<String, f64>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomMetric {
+    pub name: String,
+    pub source: MetricSource,
+    pub parser: MetricParser,
+    pub kind: MetricKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricSource {
+    File(PathBuf),
+    /// A local executable with its arguments, killed if it exceeds the timeout
+    Command {
+        command: Vec<String>,
+        timeout: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    /// Reported as read
+    Gauge,
+    /// A monotonic counter, reported as change per second
+    CounterRate,
+}
+
+pub struct CustomMetricsProvider {
+    custom_metrics: Arc<RwLock<Arc<CustomMetrics>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl CustomMetricsProvider {
+    pub fn new(metrics: Vec<CustomMetric>, update_interval: Duration) -> Self {
+        let shared_custom_metrics = Arc::new(RwLock::new(Arc::new(Default::default())));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            custom_metrics: Arc::clone(&shared_custom_metrics),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_custom_metrics),
+            tx,
+            metrics,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_custom_metrics(&self) -> Arc<CustomMetrics> {
+        Arc::clone(&self.custom_metrics.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for CustomMetricsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.custom = Some(self.current_custom_metrics());
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for CustomMetricsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for CustomMetricsProvider {
+    fn get_name(&self) -> &'static str {
+        "CustomMetricsProvider"
+    }
+}
+
+fn start_update_loop(
+    custom_metrics: Weak<RwLock<Arc<CustomMetrics>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    metrics: Vec<CustomMetric>,
+    update_interval: Duration,
+) {
+    info!("Start CustomMetricsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(custom_metrics, update_sender, metrics, update_interval).await
+    });
+}
+
+async fn update_loop(
+    custom_metrics: Weak<RwLock<Arc<CustomMetrics>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    metrics: Vec<CustomMetric>,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+    let mut counter_rates = CounterRates::default();
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let custom_metrics = match custom_metrics.upgrade() {
+            Some(custom_metrics) => custom_metrics,
+            None => {
+                info!("Couldn't get a reference to the custom metrics storage, ending update loop");
+                break;
+            }
+        };
+
+        let mut new_custom_metrics = CustomMetrics::new();
+
+        // a slow command doesn't hold up the other metrics beyond its own timeout
+        let values = future::join_all(metrics.iter().map(read_metric)).await;
+
+        for (metric, value) in metrics.iter().zip(values) {
+            let value = match value {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Failed to read custom metric {}: {:?}", metric.name, e);
+                    continue;
+                }
+            };
+
+            let value = match metric.kind {
+                MetricKind::Gauge => Some(value),
+                MetricKind::CounterRate => counter_rates.rate(&metric.name, Instant::now(), value),
+            };
+
+            if let Some(value) = value {
+                new_custom_metrics.insert(metric.name.clone(), value);
+            }
+        }
+
+        trace!("Read custom metrics: {:?}", new_custom_metrics);
+        *custom_metrics.write().unwrap() = Arc::new(new_custom_metrics);
+        update_sender.broadcast(Some(SystemTime::now())).unwrap();
+
+        interval.tick().await;
+    }
+}
+
+async fn read_metric(metric: &CustomMetric) -> anyhow::Result<f64> {
+    let content = match &metric.source {
+        MetricSource::File(path) => fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?,
+        MetricSource::Command { command, timeout } => run_command(command, *timeout).await?,
+    };
+
+    metric.parser.parse(&content)
+}
+
+async fn run_command(command: &[String], timeout: Duration) -> anyhow::Result<String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty command"))?;
+
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to execute {}", program))?;
+
+    let output = time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow!("{} timed out after {:?}", program, timeout))??;
+
+    if !output.status.success() {
+        return Err(anyhow!("{} failed with {}", program, output.status));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Turns the counter values into rates by the change since the last value
+#[derive(Debug, Default)]
+struct CounterRates {
+    last_values: HashMap<String, (Instant, f64)>,
+}
+
+impl CounterRates {
+    /// Returns `None` for the first value of a counter and after it was reset
+    fn rate(&mut self, name: &str, time: Instant, value: f64) -> Option<f64> {
+        let last = self.last_values.insert(name.to_string(), (time, value));
+        let (last_time, last_value) = last?;
+
+        let elapsed = time.duration_since(last_time);
+        if elapsed.as_nanos() == 0 || value < last_value {
+            return None;
+        }
+
+        Some((value - last_value) / elapsed.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    fn metric(source: MetricSource, parser: MetricParser) -> CustomMetric {
+        CustomMetric {
+            name: "test".into(),
+            source,
+            parser,
+            kind: MetricKind::Gauge,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_file_metric() {
+        let dir = TestDir::new();
+        dir.write_file("hit_ratio", "0.75\n");
+
+        let file_metric = metric(
+            MetricSource::File(dir.path().join("hit_ratio")),
+            MetricParser::Float,
+        );
+        assert_eq!(0.75, read_metric(&file_metric).await.unwrap());
+
+        let missing_metric = metric(
+            MetricSource::File(dir.path().join("missing")),
+            MetricParser::Float,
+        );
+        assert!(read_metric(&missing_metric).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_command_metric() {
+        let command = |args: &[&str], timeout: Duration| MetricSource::Command {
+            command: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout,
+        };
+
+        let echo_metric = metric(
+            command(&["echo", "hits=42"], Duration::from_secs(5)),
+            MetricParser::KeyValue("hits".into()),
+        );
+        assert_eq!(42.0, read_metric(&echo_metric).await.unwrap());
+
+        let failing_metric = metric(
+            command(&["false"], Duration::from_secs(5)),
+            MetricParser::Integer,
+        );
+        assert!(read_metric(&failing_metric).await.is_err());
+
+        let slow_metric = metric(
+            command(&["sleep", "5"], Duration::from_millis(100)),
+            MetricParser::Integer,
+        );
+        let start = Instant::now();
+        assert!(read_metric(&slow_metric).await.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_counter_rates() {
+        let mut counter_rates = CounterRates::default();
+        let time = Instant::now();
+
+        assert_eq!(None, counter_rates.rate("requests", time, 100.0));
+        assert_eq!(
+            Some(50.0),
+            counter_rates.rate("requests", time + Duration::from_secs(2), 200.0)
+        );
+        assert_eq!(
+            None,
+            counter_rates.rate("requests", time + Duration::from_secs(3), 10.0)
+        );
+        assert_eq!(
+            Some(5.0),
+            counter_rates.rate("requests", time + Duration::from_secs(5), 20.0)
+        );
+        assert_eq!(
+            None,
+            counter_rates.rate("responses", time + Duration::from_secs(5), 20.0)
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+
+/// How the value of a custom metric is extracted from the file content or
+/// the command output
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricParser {
+    Integer,
+    Float,
+    /// The value of the given key in `key=value` lines
+    KeyValue(String),
+    /// The number at the given RFC 6901 JSON pointer
+    JsonPointer(String),
+}
+
+impl MetricParser {
+    pub fn parse(&self, content: &str) -> anyhow::Result<f64> {
+        match self {
+            MetricParser::Integer => Ok(content.trim().parse::<i64>()? as f64),
+            MetricParser::Float => Ok(content.trim().parse()?),
+            MetricParser::KeyValue(key) => parse_key_value(content, key),
+            MetricParser::JsonPointer(pointer) => parse_json_pointer(content, pointer),
+        }
+    }
+}
+
+fn parse_key_value(content: &str, key: &str) -> anyhow::Result<f64> {
+    let value = content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+
+            match (parts.next(), parts.next()) {
+                (Some(line_key), Some(value)) if line_key.trim() == key => Some(value),
+                _ => None,
+            }
+        })
+        .next()
+        .ok_or_else(|| anyhow!("Key {} not found", key))?;
+
+    Ok(value.trim().parse()?)
+}
+
+fn parse_json_pointer(content: &str, pointer: &str) -> anyhow::Result<f64> {
+    let document: serde_json::Value = serde_json::from_str(content)?;
+
+    document
+        .pointer(pointer)
+        .ok_or_else(|| anyhow!("JSON pointer {} not found", pointer))?
+        .as_f64()
+        .ok_or_else(|| anyhow!("JSON pointer {} doesn't point to a number", pointer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(42.0, MetricParser::Integer.parse("42\n").unwrap());
+        assert_eq!(-1.0, MetricParser::Integer.parse("-1").unwrap());
+        assert!(MetricParser::Integer.parse("0.5").is_err());
+
+        assert_eq!(0.25, MetricParser::Float.parse(" 0.25\n").unwrap());
+        assert!(MetricParser::Float.parse("").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        let content = "hits=90\nhit_ratio = 0.9\nmisses=10\n";
+
+        assert_eq!(
+            0.9,
+            MetricParser::KeyValue("hit_ratio".into())
+                .parse(content)
+                .unwrap()
+        );
+        assert_eq!(
+            10.0,
+            MetricParser::KeyValue("misses".into())
+                .parse(content)
+                .unwrap()
+        );
+        assert!(MetricParser::KeyValue("hit".into()).parse(content).is_err());
+        assert!(MetricParser::KeyValue("foo".into())
+            .parse("foo=bar\n")
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_json_pointer() {
+        let content = r#"{"cache": {"hit_ratio": 0.9, "zones": [{"size": 1024}], "name": "a"}}"#;
+
+        assert_eq!(
+            0.9,
+            MetricParser::JsonPointer("/cache/hit_ratio".into())
+                .parse(content)
+                .unwrap()
+        );
+        assert_eq!(
+            1024.0,
+            MetricParser::JsonPointer("/cache/zones/0/size".into())
+                .parse(content)
+                .unwrap()
+        );
+        assert!(MetricParser::JsonPointer("/cache/name".into())
+            .parse(content)
+            .is_err());
+        assert!(MetricParser::JsonPointer("/cache/missing".into())
+            .parse(content)
+            .is_err());
+        assert!(MetricParser::JsonPointer("/cache".into())
+            .parse("no json")
+            .is_err());
+    }
+}
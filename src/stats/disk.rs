@@ -0,0 +1,269 @@
+mod diskstats;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{info, trace, warn};
+use regex::Regex;
+use tokio::sync::watch;
+use tokio::time;
+
+use super::bandwidth::{calc_counter_delta, CounterDelta, CounterWidth};
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use diskstats::{read_diskstats, DiskCounters};
+
+/// /proc/diskstats counts in 512 byte sectors regardless of the device
+const SECTOR_SIZE: f64 = 512.0;
+
+/// Throughput and utilization of a block device
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DiskStats {
+    pub name: String,
+    pub read_bytes_per_second: u64,
+    pub write_bytes_per_second: u64,
+    pub read_iops: f64,
+    pub write_iops: f64,
+    /// Share of the time the device was busy, 1.0 equals 100%
+    pub utilization: f64,
+}
+
+pub struct DiskStatsProvider {
+    disk_stats: Arc<RwLock<Arc<Vec<DiskStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl DiskStatsProvider {
+    /// Only devices whose name matches the pattern are reported
+    pub fn new<T: Into<PathBuf>>(proc_root: T, pattern: Regex, update_interval: Duration) -> Self {
+        let shared_disk_stats = Arc::new(RwLock::new(Arc::new(Default::default())));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            disk_stats: Arc::clone(&shared_disk_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_disk_stats),
+            tx,
+            proc_root.into(),
+            pattern,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_disk_stats(&self) -> Arc<Vec<DiskStats>> {
+        Arc::clone(&self.disk_stats.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for DiskStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.disks = Some(self.current_disk_stats());
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for DiskStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for DiskStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "DiskStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    disk_stats: Weak<RwLock<Arc<Vec<DiskStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    pattern: Regex,
+    update_interval: Duration,
+) {
+    info!("Start DiskStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(
+            disk_stats,
+            update_sender,
+            proc_root,
+            pattern,
+            update_interval,
+        )
+        .await
+    });
+}
+
+async fn update_loop(
+    disk_stats: Weak<RwLock<Arc<Vec<DiskStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    pattern: Regex,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+    let mut last_sample: Option<(Instant, HashMap<String, DiskCounters>)> = None;
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let disk_stats = match disk_stats.upgrade() {
+            Some(disk_stats) => disk_stats,
+            None => {
+                info!("Couldn't get a reference to the disk stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match read_diskstats(&proc_root) {
+            Ok(mut counters) => {
+                counters.retain(|name, _| pattern.is_match(name));
+                let time = Instant::now();
+
+                if let Some((last_time, last_counters)) = &last_sample {
+                    let new_disk_stats =
+                        calc_disk_stats(&counters, last_counters, time.duration_since(*last_time));
+                    trace!("Calculated disk stats: {:?}", new_disk_stats);
+
+                    *disk_stats.write().unwrap() = Arc::new(new_disk_stats);
+                    update_sender.broadcast(Some(SystemTime::now())).unwrap();
+                }
+
+                last_sample = Some((time, counters));
+            }
+            Err(e) => {
+                warn!("Failed to read disk stats: {:?}", e);
+
+                *disk_stats.write().unwrap() = Default::default();
+                update_sender.broadcast(None).unwrap();
+                last_sample = None;
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+/// Devices without a previous sample are left out
+fn calc_disk_stats(
+    counters: &HashMap<String, DiskCounters>,
+    last_counters: &HashMap<String, DiskCounters>,
+    elapsed: Duration,
+) -> Vec<DiskStats> {
+    if elapsed.as_nanos() == 0 {
+        return vec![];
+    }
+
+    let seconds = elapsed.as_secs_f64();
+    let diff = |current: u64, last: u64, counter_width: CounterWidth| match calc_counter_delta(
+        current,
+        last,
+        counter_width,
+    ) {
+        CounterDelta::Increase(diff) => diff,
+        CounterDelta::Reset(current) => current,
+    };
+    let rate = |current: u64, last: u64| diff(current, last, CounterWidth::Bits64) as f64 / seconds;
+
+    let mut disk_stats: Vec<DiskStats> = counters
+        .iter()
+        .filter_map(|(name, current)| {
+            let last = last_counters.get(name)?;
+
+            Some(DiskStats {
+                name: name.clone(),
+                read_bytes_per_second: (rate(current.sectors_read, last.sectors_read) * SECTOR_SIZE)
+                    as u64,
+                write_bytes_per_second: (rate(current.sectors_written, last.sectors_written)
+                    * SECTOR_SIZE) as u64,
+                read_iops: rate(current.reads_completed, last.reads_completed),
+                write_iops: rate(current.writes_completed, last.writes_completed),
+                utilization: (diff(current.io_ticks, last.io_ticks, CounterWidth::Bits32) as f64
+                    / 1000.0
+                    / seconds)
+                    .min(1.0),
+            })
+        })
+        .collect();
+
+    disk_stats.sort_by(|a, b| a.name.cmp(&b.name));
+
+    disk_stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calc_disk_stats() {
+        let last_counters: HashMap<String, DiskCounters> = vec![
+            (
+                "sda".to_string(),
+                DiskCounters {
+                    reads_completed: 100,
+                    sectors_read: 1000,
+                    writes_completed: 200,
+                    sectors_written: 2000,
+                    io_ticks: u64::from(u32::MAX) - 499,
+                },
+            ),
+            ("sdb".to_string(), DiskCounters::default()),
+        ]
+        .into_iter()
+        .collect();
+
+        let counters: HashMap<String, DiskCounters> = vec![
+            (
+                "sda".to_string(),
+                DiskCounters {
+                    reads_completed: 300,
+                    sectors_read: 5000,
+                    writes_completed: 600,
+                    sectors_written: 10000,
+                    io_ticks: 500,
+                },
+            ),
+            (
+                "sdb".to_string(),
+                DiskCounters {
+                    io_ticks: 5000,
+                    ..Default::default()
+                },
+            ),
+            ("sdc".to_string(), DiskCounters::default()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            vec![
+                DiskStats {
+                    name: "sda".into(),
+                    read_bytes_per_second: 1_024_000,
+                    write_bytes_per_second: 2_048_000,
+                    read_iops: 100.0,
+                    write_iops: 200.0,
+                    utilization: 0.5,
+                },
+                DiskStats {
+                    name: "sdb".into(),
+                    utilization: 1.0,
+                    ..Default::default()
+                },
+            ],
+            calc_disk_stats(&counters, &last_counters, Duration::from_secs(2))
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+/// Cumulative counters of a block device, see
+/// https://www.kernel.org/doc/Documentation/ABI/testing/procfs-diskstats
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DiskCounters {
+    pub reads_completed: u64,
+    pub sectors_read: u64,
+    pub writes_completed: u64,
+    pub sectors_written: u64,
+    /// Milliseconds spent doing I/O, a 32 bit counter
+    pub io_ticks: u64,
+}
+
+pub fn read_diskstats(proc_root: &Path) -> anyhow::Result<HashMap<String, DiskCounters>> {
+    let path = proc_root.join("diskstats");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+    parse_diskstats(&content)
+}
+
+fn parse_diskstats(content: &str) -> anyhow::Result<HashMap<String, DiskCounters>> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 14 {
+                return Err(anyhow!("Expected at least 14 diskstats fields in {}", line));
+            }
+
+            Ok((
+                fields[2].to_string(),
+                DiskCounters {
+                    reads_completed: fields[3].parse()?,
+                    sectors_read: fields[5].parse()?,
+                    writes_completed: fields[7].parse()?,
+                    sectors_written: fields[9].parse()?,
+                    io_ticks: fields[12].parse()?,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diskstats() {
+        let diskstats = parse_diskstats(
+            "   8       0 sda 1000 10 20000 500 2000 20 40000 800 0 1200 1300 0 0 0 0\n \
+             8       1 sda1 900 10 18000 450 1900 20 38000 780 2 1100 1230\n",
+        )
+        .unwrap();
+
+        assert_eq!(2, diskstats.len());
+        assert_eq!(
+            &DiskCounters {
+                reads_completed: 1000,
+                sectors_read: 20000,
+                writes_completed: 2000,
+                sectors_written: 40000,
+                io_ticks: 1200,
+            },
+            diskstats.get("sda").unwrap()
+        );
+        assert_eq!(1100, diskstats.get("sda1").unwrap().io_ticks);
+    }
+
+    #[test]
+    fn test_parse_diskstats_invalid() {
+        assert!(parse_diskstats("   8       0 sda 1000 10 20000\n").is_err());
+        assert!(parse_diskstats(
+            "   8       0 sda 1000 10 foo 500 2000 20 40000 800 0 1200 1300\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::task::{self, JoinHandle};
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+
+/// statvfs blocks for as long as e.g. an unreachable NFS server doesn't answer
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(5);
+
+type StatvfsCall = JoinHandle<anyhow::Result<FilesystemStats>>;
+
+/// Space and inode usage of the filesystem containing a configured path
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FilesystemStats {
+    pub path: String,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    /// Free bytes available to unprivileged users
+    pub available_bytes: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    pub available_inodes: u64,
+}
+
+pub struct FilesystemStatsProvider {
+    filesystem_stats: Arc<RwLock<Arc<Vec<FilesystemStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl FilesystemStatsProvider {
+    pub fn new(paths: Vec<PathBuf>, update_interval: Duration) -> Self {
+        let shared_filesystem_stats = Arc::new(RwLock::new(Arc::new(Default::default())));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            filesystem_stats: Arc::clone(&shared_filesystem_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_filesystem_stats),
+            tx,
+            paths,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_filesystem_stats(&self) -> Arc<Vec<FilesystemStats>> {
+        Arc::clone(&self.filesystem_stats.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for FilesystemStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.filesystems = Some(self.current_filesystem_stats());
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for FilesystemStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for FilesystemStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "FilesystemStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    filesystem_stats: Weak<RwLock<Arc<Vec<FilesystemStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    paths: Vec<PathBuf>,
+    update_interval: Duration,
+) {
+    info!("Start FilesystemStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(filesystem_stats, update_sender, paths, update_interval).await
+    });
+}
+
+async fn update_loop(
+    filesystem_stats: Weak<RwLock<Arc<Vec<FilesystemStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    paths: Vec<PathBuf>,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+    let mut pending_calls = HashMap::new();
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let filesystem_stats = match filesystem_stats.upgrade() {
+            Some(filesystem_stats) => filesystem_stats,
+            None => {
+                info!(
+                    "Couldn't get a reference to the filesystem stats storage, ending update loop"
+                );
+                break;
+            }
+        };
+
+        let new_filesystem_stats = read_filesystem_stats(&paths, &mut pending_calls).await;
+        trace!("Read filesystem stats: {:?}", new_filesystem_stats);
+        *filesystem_stats.write().unwrap() = Arc::new(new_filesystem_stats);
+        update_sender.broadcast(Some(SystemTime::now())).unwrap();
+
+        interval.tick().await;
+    }
+}
+
+/// Paths that can't be read are left out, e.g. while a mount is missing or
+/// hangs. Calls that don't return in time are awaited again on the next read
+/// instead of blocking another thread on the same mount.
+async fn read_filesystem_stats(
+    paths: &[PathBuf],
+    pending_calls: &mut HashMap<PathBuf, StatvfsCall>,
+) -> Vec<FilesystemStats> {
+    // the calls run concurrently on the blocking thread pool
+    for path in paths {
+        if !pending_calls.contains_key(path) {
+            let blocking_path = path.clone();
+            let call = task::spawn_blocking(move || statvfs(&blocking_path));
+            pending_calls.insert(path.clone(), call);
+        }
+    }
+
+    let deadline = time::Instant::now() + STATVFS_TIMEOUT;
+    let mut filesystem_stats = vec![];
+
+    for path in paths {
+        let call = match pending_calls.get_mut(path) {
+            Some(call) => call,
+            None => continue,
+        };
+
+        let result = match time::timeout_at(deadline, call).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    "Reading filesystem stats of {} timed out",
+                    path.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        pending_calls.remove(path);
+
+        match result {
+            Ok(Ok(stats)) => filesystem_stats.push(stats),
+            Ok(Err(e)) => warn!("Failed to read filesystem stats: {:?}", e),
+            Err(e) => warn!("Failed to join the filesystem stats call: {:?}", e),
+        }
+    }
+
+    filesystem_stats
+}
+
+fn statvfs(path: &Path) -> anyhow::Result<FilesystemStats> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("statvfs failed for {}", path.to_string_lossy()));
+    }
+
+    let fragment_size = stat.f_frsize as u64;
+
+    Ok(FilesystemStats {
+        path: path.to_string_lossy().into_owned(),
+        total_bytes: stat.f_blocks as u64 * fragment_size,
+        free_bytes: stat.f_bfree as u64 * fragment_size,
+        available_bytes: stat.f_bavail as u64 * fragment_size,
+        total_inodes: stat.f_files as u64,
+        free_inodes: stat.f_ffree as u64,
+        available_inodes: stat.f_favail as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[tokio::test]
+    async fn test_read_filesystem_stats() {
+        let dir = TestDir::new();
+        let mut pending_calls = HashMap::new();
+
+        let filesystem_stats = read_filesystem_stats(
+            &[dir.path().to_path_buf(), dir.path().join("missing")],
+            &mut pending_calls,
+        )
+        .await;
+
+        assert_eq!(1, filesystem_stats.len());
+        assert!(pending_calls.is_empty());
+
+        let stats = &filesystem_stats[0];
+        assert_eq!(dir.path().to_string_lossy(), stats.path);
+        assert!(stats.total_bytes > 0);
+        assert!(stats.free_bytes <= stats.total_bytes);
+        assert!(stats.available_bytes <= stats.free_bytes);
+        assert!(stats.free_inodes <= stats.total_inodes);
+    }
+}
@@ -0,0 +1,202 @@
+mod loadavg;
+mod pressure;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use loadavg::read_loadavg;
+use pressure::read_pressure;
+
+pub use loadavg::LoadAverage;
+pub use pressure::{Pressure, PressureValues};
+
+/// Load average and pressure stall information, the pressure values are
+/// absent on kernels without PSI support
+#[derive(Debug, Default, PartialEq)]
+pub struct LoadStats {
+    pub load_average: LoadAverage,
+    pub cpu_pressure: Option<Pressure>,
+    pub memory_pressure: Option<Pressure>,
+    pub io_pressure: Option<Pressure>,
+}
+
+pub struct LoadStatsProvider {
+    load_stats: Arc<RwLock<Option<Arc<LoadStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl LoadStatsProvider {
+    pub fn new<T: Into<PathBuf>>(proc_root: T, update_interval: Duration) -> Self {
+        let shared_load_stats = Arc::new(RwLock::new(None));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            load_stats: Arc::clone(&shared_load_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_load_stats),
+            tx,
+            proc_root.into(),
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_load_stats(&self) -> Option<Arc<LoadStats>> {
+        self.load_stats.read().unwrap().clone()
+    }
+}
+
+impl NodeStatsUpdater for LoadStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.load = self.current_load_stats();
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for LoadStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for LoadStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "LoadStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    load_stats: Weak<RwLock<Option<Arc<LoadStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    info!("Start LoadStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(load_stats, update_sender, proc_root, update_interval).await
+    });
+}
+
+async fn update_loop(
+    load_stats: Weak<RwLock<Option<Arc<LoadStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let load_stats = match load_stats.upgrade() {
+            Some(load_stats) => load_stats,
+            None => {
+                info!("Couldn't get a reference to the load stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match read_load_stats(&proc_root) {
+            Ok(new_load_stats) => {
+                trace!("Read load stats: {:?}", new_load_stats);
+                *load_stats.write().unwrap() = Some(Arc::new(new_load_stats));
+                update_sender.broadcast(Some(SystemTime::now())).unwrap();
+            }
+            Err(e) => {
+                warn!("Failed to read load stats: {:?}", e);
+
+                *load_stats.write().unwrap() = None;
+                update_sender.broadcast(None).unwrap();
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+fn read_load_stats(proc_root: &Path) -> anyhow::Result<LoadStats> {
+    Ok(LoadStats {
+        load_average: read_loadavg(proc_root)?,
+        cpu_pressure: read_optional_pressure(proc_root, "cpu"),
+        memory_pressure: read_optional_pressure(proc_root, "memory"),
+        io_pressure: read_optional_pressure(proc_root, "io"),
+    })
+}
+
+fn read_optional_pressure(proc_root: &Path, resource: &str) -> Option<Pressure> {
+    read_pressure(proc_root, resource).unwrap_or_else(|e| {
+        warn!("Failed to read {} pressure: {:?}", resource, e);
+
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_read_load_stats_without_psi() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("loadavg", "0.50 0.25 0.10 1/100 4242\n");
+
+        let load_stats = read_load_stats(proc_root.path()).unwrap();
+
+        assert_eq!(0.5, load_stats.load_average.one);
+        assert_eq!(None, load_stats.cpu_pressure);
+        assert_eq!(None, load_stats.memory_pressure);
+        assert_eq!(None, load_stats.io_pressure);
+    }
+
+    #[test]
+    fn test_read_load_stats_with_psi() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("loadavg", "0.50 0.25 0.10 1/100 4242\n");
+        proc_root.write_file(
+            "pressure/cpu",
+            "some avg10=1.00 avg60=2.00 avg300=3.00 total=1\n",
+        );
+        proc_root.write_file(
+            "pressure/memory",
+            "some avg10=0.00 avg60=0.00 avg300=0.00 total=0\nfull avg10=4.00 avg60=5.00 avg300=6.00 total=0\n",
+        );
+        proc_root.write_file("pressure/io", "garbage\n");
+
+        let load_stats = read_load_stats(proc_root.path()).unwrap();
+
+        assert_eq!(3.0, load_stats.cpu_pressure.unwrap().some.avg300);
+        assert_eq!(
+            Some(PressureValues {
+                avg10: 4.0,
+                avg60: 5.0,
+                avg300: 6.0,
+            }),
+            load_stats.memory_pressure.unwrap().full
+        );
+        assert_eq!(None, load_stats.io_pressure);
+    }
+
+    #[test]
+    fn test_read_load_stats_missing_loadavg() {
+        let proc_root = TestDir::new();
+
+        assert!(read_load_stats(proc_root.path()).is_err());
+    }
+}
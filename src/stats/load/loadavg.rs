@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+    pub runnable_tasks: u64,
+    pub total_tasks: u64,
+}
+
+pub fn read_loadavg(proc_root: &Path) -> anyhow::Result<LoadAverage> {
+    let path = proc_root.join("loadavg");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+    parse_loadavg(&content)
+}
+
+fn parse_loadavg(content: &str) -> anyhow::Result<LoadAverage> {
+    let fields: Vec<&str> = content.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(anyhow!(
+            "Expected at least 4 loadavg fields, got {}",
+            fields.len()
+        ));
+    }
+
+    let mut tasks = fields[3].splitn(2, '/');
+    let (runnable_tasks, total_tasks) = match (tasks.next(), tasks.next()) {
+        (Some(runnable), Some(total)) => (runnable.parse()?, total.parse()?),
+        _ => return Err(anyhow!("Invalid loadavg task field {}", fields[3])),
+    };
+
+    Ok(LoadAverage {
+        one: fields[0].parse()?,
+        five: fields[1].parse()?,
+        fifteen: fields[2].parse()?,
+        runnable_tasks,
+        total_tasks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_loadavg() {
+        assert_eq!(
+            LoadAverage {
+                one: 0.52,
+                five: 1.25,
+                fifteen: 2.0,
+                runnable_tasks: 3,
+                total_tasks: 712,
+            },
+            parse_loadavg("0.52 1.25 2.00 3/712 123456\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_loadavg_invalid() {
+        assert!(parse_loadavg("0.52 1.25\n").is_err());
+        assert!(parse_loadavg("0.52 1.25 2.00 3 123456\n").is_err());
+        assert!(parse_loadavg("0.52 foo 2.00 3/712 123456\n").is_err());
+    }
+}
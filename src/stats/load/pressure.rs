@@ -0,0 +1,152 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+/// Pressure stall information of a single resource as found in /proc/pressure
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Pressure {
+    pub some: PressureValues,
+    /// Not reported for cpu pressure on kernels older than 5.13
+    pub full: Option<PressureValues>,
+}
+
+/// Share of wall time in percent in which tasks stalled on the resource
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PressureValues {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+/// Reads the pressure file of the given resource, returns `Ok(None)`
+/// if the kernel doesn't support PSI
+pub fn read_pressure(proc_root: &Path, resource: &str) -> anyhow::Result<Option<Pressure>> {
+    let path = proc_root.join("pressure").join(resource);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if is_unsupported(&e) => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.to_string_lossy()))
+        }
+    };
+
+    parse_pressure(&content).map(Some)
+}
+
+/// PSI is either compiled out (missing files) or disabled via `psi=0`,
+/// in which case reading the files fails with EOPNOTSUPP
+fn is_unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::EOPNOTSUPP)
+}
+
+fn parse_pressure(content: &str) -> anyhow::Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+
+        match fields.next() {
+            Some("some") => some = Some(parse_pressure_values(fields)?),
+            Some("full") => full = Some(parse_pressure_values(fields)?),
+            _ => {}
+        }
+    }
+
+    Ok(Pressure {
+        some: some.ok_or_else(|| anyhow!("Missing some pressure line"))?,
+        full,
+    })
+}
+
+fn parse_pressure_values<'a, T: Iterator<Item = &'a str>>(
+    fields: T,
+) -> anyhow::Result<PressureValues> {
+    let mut values = PressureValues::default();
+
+    for field in fields {
+        let mut parts = field.splitn(2, '=');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => return Err(anyhow!("Invalid pressure field {}", field)),
+        };
+
+        match key {
+            "avg10" => values.avg10 = value.parse()?,
+            "avg60" => values.avg60 = value.parse()?,
+            "avg300" => values.avg300 = value.parse()?,
+            _ => {}
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_parse_pressure() {
+        let pressure = parse_pressure(
+            "some avg10=1.50 avg60=0.75 avg300=0.10 total=123456
+full avg10=0.50 avg60=0.25 avg300=0.00 total=23456
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Pressure {
+                some: PressureValues {
+                    avg10: 1.5,
+                    avg60: 0.75,
+                    avg300: 0.1,
+                },
+                full: Some(PressureValues {
+                    avg10: 0.5,
+                    avg60: 0.25,
+                    avg300: 0.0,
+                }),
+            },
+            pressure
+        );
+    }
+
+    #[test]
+    fn test_parse_pressure_without_full() {
+        let pressure =
+            parse_pressure("some avg10=1.50 avg60=0.75 avg300=0.10 total=123456\n").unwrap();
+
+        assert_eq!(None, pressure.full);
+    }
+
+    #[test]
+    fn test_parse_pressure_invalid() {
+        assert!(parse_pressure("full avg10=0.50 avg60=0.25 avg300=0.00 total=0\n").is_err());
+        assert!(parse_pressure("some avg10=foo avg60=0.25 avg300=0.00 total=0\n").is_err());
+    }
+
+    #[test]
+    fn test_read_pressure_unsupported() {
+        let proc_root = TestDir::new();
+
+        assert_eq!(None, read_pressure(proc_root.path(), "cpu").unwrap());
+    }
+
+    #[test]
+    fn test_read_pressure() {
+        let proc_root = TestDir::new();
+        proc_root.write_file(
+            "pressure/io",
+            "some avg10=1.50 avg60=0.75 avg300=0.10 total=123456\n",
+        );
+
+        let pressure = read_pressure(proc_root.path(), "io").unwrap().unwrap();
+
+        assert_eq!(1.5, pressure.some.avg10);
+    }
+}
@@ -0,0 +1,212 @@
+mod meminfo;
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use meminfo::{read_meminfo, MemInfo};
+
+/// Memory and swap usage in bytes
+#[derive(Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub available: u64,
+    pub cached: u64,
+    pub dirty: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+pub struct MemoryStatsProvider {
+    memory_stats: Arc<RwLock<Option<Arc<MemoryStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl MemoryStatsProvider {
+    pub fn new<T: Into<PathBuf>>(proc_root: T, update_interval: Duration) -> Self {
+        let shared_memory_stats = Arc::new(RwLock::new(None));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            memory_stats: Arc::clone(&shared_memory_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_memory_stats),
+            tx,
+            proc_root.into(),
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_memory_stats(&self) -> Option<Arc<MemoryStats>> {
+        self.memory_stats.read().unwrap().clone()
+    }
+}
+
+impl NodeStatsUpdater for MemoryStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.memory = self.current_memory_stats();
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for MemoryStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for MemoryStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "MemoryStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    memory_stats: Weak<RwLock<Option<Arc<MemoryStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    info!("Start MemoryStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(memory_stats, update_sender, proc_root, update_interval).await
+    });
+}
+
+async fn update_loop(
+    memory_stats: Weak<RwLock<Option<Arc<MemoryStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let memory_stats = match memory_stats.upgrade() {
+            Some(memory_stats) => memory_stats,
+            None => {
+                info!("Couldn't get a reference to the memory stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match read_meminfo(&proc_root).and_then(|meminfo| calc_memory_stats(&meminfo)) {
+            Ok(new_memory_stats) => {
+                trace!("Read memory stats: {:?}", new_memory_stats);
+                *memory_stats.write().unwrap() = Some(Arc::new(new_memory_stats));
+                update_sender.broadcast(Some(SystemTime::now())).unwrap();
+            }
+            Err(e) => {
+                warn!("Failed to read memory stats: {:?}", e);
+
+                *memory_stats.write().unwrap() = None;
+                update_sender.broadcast(None).unwrap();
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+fn calc_memory_stats(meminfo: &MemInfo) -> anyhow::Result<MemoryStats> {
+    Ok(MemoryStats {
+        total: meminfo.get("MemTotal")?,
+        available: meminfo.get("MemAvailable")?,
+        cached: meminfo.get("Cached")?,
+        dirty: meminfo.get("Dirty")?,
+        swap_total: meminfo.get("SwapTotal")?,
+        swap_free: meminfo.get("SwapFree")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::test_util::TestDir;
+
+    #[tokio::test]
+    async fn test_memory_stats_provider() {
+        let proc_root = TestDir::new();
+        proc_root.write_file(
+            "meminfo",
+            "MemTotal: 4 kB\nMemAvailable: 3 kB\nCached: 2 kB\nDirty: 1 kB\nSwapTotal: 2 kB\nSwapFree: 1 kB\n",
+        );
+
+        let memory_stats_provider =
+            MemoryStatsProvider::new(proc_root.path(), Duration::from_secs(1));
+
+        let loop_start = Instant::now();
+        let expected_memory_stats = MemoryStats {
+            total: 4096,
+            available: 3072,
+            cached: 2048,
+            dirty: 1024,
+            swap_total: 2048,
+            swap_free: 1024,
+        };
+        loop {
+            time::delay_for(Duration::from_millis(50)).await;
+            let memory_stats = memory_stats_provider.current_memory_stats();
+
+            if loop_start.elapsed() >= Duration::from_secs(2) {
+                panic!("Failed to retrieve the expected memory stats in time");
+            }
+
+            if memory_stats.as_deref() == Some(&expected_memory_stats) {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_stats_provider_read_error() {
+        let proc_root = TestDir::new();
+        proc_root.write_file(
+            "meminfo",
+            "MemTotal: 4 kB\nMemAvailable: 3 kB\nCached: 2 kB\nDirty: 1 kB\nSwapTotal: 2 kB\nSwapFree: 1 kB\n",
+        );
+
+        let memory_stats_provider =
+            MemoryStatsProvider::new(proc_root.path(), Duration::from_millis(10));
+        let mut update_receiver = memory_stats_provider.get_update_channel_receiver();
+
+        update_receiver.recv().await; // the initial value
+        update_receiver.recv().await;
+        assert!(memory_stats_provider.current_memory_stats().is_some());
+
+        proc_root.write_file("meminfo", "MemTotal: 4 kB\n");
+        while let Some(Some(_)) = update_receiver.recv().await {}
+
+        assert_eq!(None, memory_stats_provider.current_memory_stats());
+    }
+
+    #[test]
+    fn test_calc_memory_stats_missing_key() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("meminfo", "MemTotal: 4 kB\n");
+
+        let meminfo = read_meminfo(proc_root.path()).unwrap();
+
+        assert!(calc_memory_stats(&meminfo).is_err());
+    }
+}
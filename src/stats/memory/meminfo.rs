@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemInfo {
+    values: HashMap<String, u64>,
+}
+
+impl MemInfo {
+    /// Returns the value of the given meminfo key in bytes
+    pub fn get(&self, key: &str) -> anyhow::Result<u64> {
+        self.values
+            .get(key)
+            .copied()
+            .ok_or_else(|| anyhow!("Missing meminfo key {}", key))
+    }
+}
+
+pub fn read_meminfo(proc_root: &Path) -> anyhow::Result<MemInfo> {
+    let path = proc_root.join("meminfo");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+    parse_meminfo(&content)
+}
+
+fn parse_meminfo(content: &str) -> anyhow::Result<MemInfo> {
+    let mut values = HashMap::new();
+
+    for line in content.lines() {
+        let mut parts = line.splitn(2, ':');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value),
+            _ => continue,
+        };
+
+        let mut fields = value.split_whitespace();
+        let number: u64 = fields
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for meminfo key {}", key))?
+            .parse()
+            .with_context(|| format!("Invalid value for meminfo key {}", key))?;
+
+        let number = match fields.next() {
+            Some("kB") => number * 1024,
+            Some(unit) => return Err(anyhow!("Unknown unit {} for meminfo key {}", unit, key)),
+            None => number,
+        };
+
+        values.insert(key.to_string(), number);
+    }
+
+    Ok(MemInfo { values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    const MEMINFO: &str = "MemTotal:       16314240 kB
+MemFree:         1234567 kB
+MemAvailable:    8157120 kB
+Cached:          4000000 kB
+SwapCached:            0 kB
+Dirty:              1024 kB
+SwapTotal:       2097148 kB
+SwapFree:        2097148 kB
+HugePages_Total:       0
+";
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = parse_meminfo(MEMINFO).unwrap();
+
+        assert_eq!(16314240 * 1024, meminfo.get("MemTotal").unwrap());
+        assert_eq!(1024 * 1024, meminfo.get("Dirty").unwrap());
+        assert_eq!(0, meminfo.get("HugePages_Total").unwrap());
+        assert!(meminfo.get("Foo").is_err());
+    }
+
+    #[test]
+    fn test_parse_meminfo_invalid_number() {
+        assert!(parse_meminfo("MemTotal: foo kB\n").is_err());
+    }
+
+    #[test]
+    fn test_read_meminfo_from_proc_root() {
+        let proc_root = TestDir::new();
+        proc_root.write_file("meminfo", MEMINFO);
+
+        let meminfo = read_meminfo(proc_root.path()).unwrap();
+
+        assert_eq!(8157120 * 1024, meminfo.get("MemAvailable").unwrap());
+    }
+
+    #[test]
+    fn test_read_meminfo_non_existent() {
+        assert!(read_meminfo(Path::new("invalid")).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+mod stub_status;
+
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::anyhow;
+use hyper::{body, Client, Uri};
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::bandwidth::{calc_counter_delta, CounterDelta, CounterWidth};
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use stub_status::{parse_stub_status, StubStatus};
+
+/// Connections of the nginx stub_status page and the rates of its counters
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NginxStats {
+    pub active_connections: u64,
+    pub reading: u64,
+    pub writing: u64,
+    pub waiting: u64,
+    pub accepts_per_second: f64,
+    pub handled_per_second: f64,
+    pub requests_per_second: f64,
+}
+
+pub struct NginxStatsProvider {
+    /// None while nginx can't be reached
+    nginx_stats: Arc<RwLock<Option<Arc<NginxStats>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl NginxStatsProvider {
+    /// Fetches the stub_status page from the url, giving up after the timeout
+    pub fn new(url: Uri, timeout: Duration, update_interval: Duration) -> Self {
+        let shared_nginx_stats = Arc::new(RwLock::new(None));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            nginx_stats: Arc::clone(&shared_nginx_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_nginx_stats),
+            tx,
+            url,
+            timeout,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_nginx_stats(&self) -> Option<Arc<NginxStats>> {
+        self.nginx_stats.read().unwrap().clone()
+    }
+}
+
+impl NodeStatsUpdater for NginxStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.nginx = self.current_nginx_stats();
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for NginxStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for NginxStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "NginxStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    nginx_stats: Weak<RwLock<Option<Arc<NginxStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    url: Uri,
+    timeout: Duration,
+    update_interval: Duration,
+) {
+    info!("Start NginxStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(nginx_stats, update_sender, url, timeout, update_interval).await
+    });
+}
+
+async fn update_loop(
+    nginx_stats: Weak<RwLock<Option<Arc<NginxStats>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    url: Uri,
+    timeout: Duration,
+    update_interval: Duration,
+) {
+    let client = Client::new();
+    let mut interval = time::interval(update_interval);
+    let mut last_sample: Option<(Instant, StubStatus)> = None;
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let nginx_stats = match nginx_stats.upgrade() {
+            Some(nginx_stats) => nginx_stats,
+            None => {
+                info!("Couldn't get a reference to the nginx stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match fetch_stub_status(&client, &url, timeout).await {
+            Ok(stub_status) => {
+                let time = Instant::now();
+
+                if let Some((last_time, last_stub_status)) = &last_sample {
+                    let new_nginx_stats = calc_nginx_stats(
+                        &stub_status,
+                        last_stub_status,
+                        time.duration_since(*last_time),
+                    );
+                    trace!("Calculated nginx stats: {:?}", new_nginx_stats);
+
+                    *nginx_stats.write().unwrap() = Some(Arc::new(new_nginx_stats));
+                    update_sender.broadcast(Some(SystemTime::now())).unwrap();
+                }
+
+                last_sample = Some((time, stub_status));
+            }
+            Err(e) => {
+                warn!("Failed to fetch nginx stub_status from {}: {:?}", url, e);
+
+                // the rates start over once nginx is back
+                *nginx_stats.write().unwrap() = None;
+                update_sender.broadcast(None).unwrap();
+                last_sample = None;
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+async fn fetch_stub_status<C>(
+    client: &Client<C>,
+    url: &Uri,
+    timeout: Duration,
+) -> anyhow::Result<StubStatus>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    let fetch = async {
+        let response = client.get(url.clone()).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Unexpected response status {}", response.status()));
+        }
+
+        let body = body::to_bytes(response.into_body()).await?;
+
+        Ok(String::from_utf8(body.to_vec())?)
+    };
+
+    let content = time::timeout(timeout, fetch)
+        .await
+        .map_err(|_| anyhow!("Timed out after {:?}", timeout))??;
+
+    parse_stub_status(&content)
+}
+
+fn calc_nginx_stats(
+    stub_status: &StubStatus,
+    last_stub_status: &StubStatus,
+    elapsed: Duration,
+) -> NginxStats {
+    // a restarted nginx starts counting from zero again
+    let rate = |current: u64, last: u64| {
+        let diff = match calc_counter_delta(current, last, CounterWidth::Bits64) {
+            CounterDelta::Increase(diff) => diff,
+            CounterDelta::Reset(current) => current,
+        };
+
+        if elapsed.as_nanos() == 0 {
+            0.0
+        } else {
+            diff as f64 / elapsed.as_secs_f64()
+        }
+    };
+
+    NginxStats {
+        active_connections: stub_status.active,
+        reading: stub_status.reading,
+        writing: stub_status.writing,
+        waiting: stub_status.waiting,
+        accepts_per_second: rate(stub_status.accepts, last_stub_status.accepts),
+        handled_per_second: rate(stub_status.handled, last_stub_status.handled),
+        requests_per_second: rate(stub_status.requests, last_stub_status.requests),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server, StatusCode};
+
+    /// Serves the stub_status page on /nginx_status and 404 on everything else
+    fn start_status_server(requests: u64) -> SocketAddr {
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, Infallible>(service_fn(move |request| async move {
+                let response = if request.uri().path() == "/nginx_status" {
+                    Response::new(Body::from(format!(
+                        "Active connections: 3 \nserver accepts handled requests\n 10 10 {} \nReading: 0 Writing: 1 Waiting: 2 \n",
+                        requests
+                    )))
+                } else {
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    response
+                };
+
+                Ok::<_, Infallible>(response)
+            }))
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        address
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stub_status() {
+        let address = start_status_server(42);
+        let client = Client::new();
+        let url = |path: &str| format!("http://{}{}", address, path).parse().unwrap();
+
+        let stub_status = fetch_stub_status(&client, &url("/nginx_status"), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(42, stub_status.requests);
+        assert_eq!(3, stub_status.active);
+
+        assert!(
+            fetch_stub_status(&client, &url("/missing"), Duration::from_secs(5))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider() {
+        let address = start_status_server(42);
+        let provider = NginxStatsProvider::new(
+            format!("http://{}/nginx_status", address).parse().unwrap(),
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        );
+        let mut update_receiver = provider.get_update_channel_receiver();
+
+        update_receiver.recv().await; // the initial value
+        update_receiver.recv().await;
+
+        let nginx_stats = provider.current_nginx_stats().unwrap();
+        assert_eq!(3, nginx_stats.active_connections);
+        assert_eq!(2, nginx_stats.waiting);
+        assert_eq!(0.0, nginx_stats.requests_per_second);
+    }
+
+    #[tokio::test]
+    async fn test_provider_fetch_error() {
+        let address = start_status_server(42);
+        let provider = NginxStatsProvider::new(
+            format!("http://{}/missing", address).parse().unwrap(),
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        );
+        let mut update_receiver = provider.get_update_channel_receiver();
+
+        update_receiver.recv().await; // the initial value
+        update_receiver.recv().await;
+
+        assert_eq!(None, provider.current_nginx_stats());
+    }
+
+    #[test]
+    fn test_calc_nginx_stats() {
+        let last_stub_status = StubStatus {
+            accepts: 100,
+            handled: 100,
+            requests: 1000,
+            ..Default::default()
+        };
+        let stub_status = StubStatus {
+            active: 5,
+            accepts: 120,
+            handled: 110,
+            requests: 1400,
+            reading: 1,
+            writing: 2,
+            waiting: 2,
+        };
+
+        assert_eq!(
+            NginxStats {
+                active_connections: 5,
+                reading: 1,
+                writing: 2,
+                waiting: 2,
+                accepts_per_second: 10.0,
+                handled_per_second: 5.0,
+                requests_per_second: 200.0,
+            },
+            calc_nginx_stats(&stub_status, &last_stub_status, Duration::from_secs(2))
+        );
+
+        let restarted = StubStatus {
+            requests: 20,
+            ..Default::default()
+        };
+        assert_eq!(
+            10.0,
+            calc_nginx_stats(&restarted, &last_stub_status, Duration::from_secs(2))
+                .requests_per_second
+        );
+    }
+}
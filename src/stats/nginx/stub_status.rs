@@ -0,0 +1,111 @@
+use anyhow::anyhow;
+
+/// The values of the nginx stub_status page
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StubStatus {
+    pub active: u64,
+    pub accepts: u64,
+    pub handled: u64,
+    pub requests: u64,
+    pub reading: u64,
+    pub writing: u64,
+    pub waiting: u64,
+}
+
+/// Parses the stub_status output, e.g.
+///
+/// ```text
+/// Active connections: 291
+/// server accepts handled requests
+///  16630948 16630948 31070465
+/// Reading: 6 Writing: 179 Waiting: 106
+/// ```
+pub fn parse_stub_status(content: &str) -> anyhow::Result<StubStatus> {
+    let mut lines = content.lines().map(str::trim);
+
+    let active = lines
+        .next()
+        .and_then(|line| line.strip_prefix("Active connections:"))
+        .ok_or_else(|| anyhow!("Missing active connections"))?
+        .trim()
+        .parse()?;
+
+    if lines.next() != Some("server accepts handled requests") {
+        return Err(anyhow!("Missing server counters header"));
+    }
+
+    let counters = lines
+        .next()
+        .ok_or_else(|| anyhow!("Missing server counters"))?
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()?;
+    let (accepts, handled, requests) = match counters.as_slice() {
+        [accepts, handled, requests] => (*accepts, *handled, *requests),
+        _ => {
+            return Err(anyhow!(
+                "Expected 3 server counters, got {}",
+                counters.len()
+            ))
+        }
+    };
+
+    let connections: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| anyhow!("Missing connection states"))?
+        .split_whitespace()
+        .collect();
+    let (reading, writing, waiting) = match connections.as_slice() {
+        ["Reading:", reading, "Writing:", writing, "Waiting:", waiting] => {
+            (reading.parse()?, writing.parse()?, waiting.parse()?)
+        }
+        _ => return Err(anyhow!("Invalid connection states {:?}", connections)),
+    };
+
+    Ok(StubStatus {
+        active,
+        accepts,
+        handled,
+        requests,
+        reading,
+        writing,
+        waiting,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stub_status() {
+        assert_eq!(
+            StubStatus {
+                active: 291,
+                accepts: 16630948,
+                handled: 16630946,
+                requests: 31070465,
+                reading: 6,
+                writing: 179,
+                waiting: 106,
+            },
+            parse_stub_status(
+                "Active connections: 291 \nserver accepts handled requests\n 16630948 16630946 31070465 \nReading: 6 Writing: 179 Waiting: 106 \n"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_stub_status_invalid() {
+        assert!(parse_stub_status("<html>Not Found</html>").is_err());
+        assert!(parse_stub_status(
+            "Active connections: 1\nserver accepts handled requests\n 1 1\nReading: 0 Writing: 1 Waiting: 0\n"
+        )
+        .is_err());
+        assert!(parse_stub_status(
+            "Active connections: 1\nserver accepts handled requests\n 1 1 1\nReading: 0 Writing: 1\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,202 @@
+mod proc_net_tcp;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace, warn};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use proc_net_tcp::count_tcp_states;
+
+pub use proc_net_tcp::TcpStateCounts;
+
+pub struct TcpConnectionStatsProvider {
+    tcp_connection_stats: Arc<RwLock<Option<Arc<TcpStateCounts>>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl TcpConnectionStatsProvider {
+    /// Only sockets bound to one of the local ports are counted, all sockets
+    /// are counted if the list is empty
+    pub fn new<T: Into<PathBuf>>(
+        proc_root: T,
+        local_ports: Vec<u16>,
+        update_interval: Duration,
+    ) -> Self {
+        let shared_tcp_connection_stats = Arc::new(RwLock::new(None));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            tcp_connection_stats: Arc::clone(&shared_tcp_connection_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_tcp_connection_stats),
+            tx,
+            proc_root.into(),
+            local_ports,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_tcp_connection_stats(&self) -> Option<Arc<TcpStateCounts>> {
+        self.tcp_connection_stats.read().unwrap().clone()
+    }
+}
+
+impl NodeStatsUpdater for TcpConnectionStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.tcp_connections = self.current_tcp_connection_stats();
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for TcpConnectionStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for TcpConnectionStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "TcpConnectionStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    tcp_connection_stats: Weak<RwLock<Option<Arc<TcpStateCounts>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    local_ports: Vec<u16>,
+    update_interval: Duration,
+) {
+    info!("Start TcpConnectionStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(
+            tcp_connection_stats,
+            update_sender,
+            proc_root,
+            local_ports,
+            update_interval,
+        )
+        .await
+    });
+}
+
+async fn update_loop(
+    tcp_connection_stats: Weak<RwLock<Option<Arc<TcpStateCounts>>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    proc_root: PathBuf,
+    local_ports: Vec<u16>,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let tcp_connection_stats = match tcp_connection_stats.upgrade() {
+            Some(tcp_connection_stats) => tcp_connection_stats,
+            None => {
+                info!("Couldn't get a reference to the tcp connection stats storage, ending update loop");
+                break;
+            }
+        };
+
+        match read_tcp_connection_stats(&proc_root, &local_ports) {
+            Ok(new_tcp_connection_stats) => {
+                trace!("Read tcp connection stats: {:?}", new_tcp_connection_stats);
+                *tcp_connection_stats.write().unwrap() = Some(Arc::new(new_tcp_connection_stats));
+                update_sender.broadcast(Some(SystemTime::now())).unwrap();
+            }
+            Err(e) => {
+                warn!("Failed to read tcp connection stats: {:?}", e);
+
+                *tcp_connection_stats.write().unwrap() = None;
+                update_sender.broadcast(None).unwrap();
+            }
+        }
+
+        interval.tick().await;
+    }
+}
+
+fn read_tcp_connection_stats(
+    proc_root: &Path,
+    local_ports: &[u16],
+) -> anyhow::Result<TcpStateCounts> {
+    let mut counts = TcpStateCounts::default();
+    count_tcp_states(&proc_root.join("net/tcp"), local_ports, &mut counts)?;
+
+    // absent if ipv6 is disabled
+    let tcp6 = proc_root.join("net/tcp6");
+    if tcp6.exists() {
+        count_tcp_states(&tcp6, local_ports, &mut counts)?;
+    }
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    const HEADER: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n";
+
+    #[test]
+    fn test_read_tcp_connection_stats() {
+        let proc_root = TestDir::new();
+        proc_root.write_file(
+            "net/tcp",
+            &format!(
+                "{}   0: 0A00000A:01BB 0B00000A:C350 01 00000000:00000000 00:00000000 00000000 0 0 1 1\n",
+                HEADER
+            ),
+        );
+
+        assert_eq!(
+            1,
+            read_tcp_connection_stats(proc_root.path(), &[])
+                .unwrap()
+                .established
+        );
+
+        proc_root.write_file(
+            "net/tcp6",
+            &format!(
+                "{}   0: 00000000000000000000000001000000:01BB 00000000000000000000000001000000:C350 06 00000000:00000000 00:00000000 00000000 0 0 1 1\n",
+                HEADER
+            ),
+        );
+
+        let counts = read_tcp_connection_stats(proc_root.path(), &[443]).unwrap();
+        assert_eq!(1, counts.established);
+        assert_eq!(1, counts.time_wait);
+        assert_eq!(
+            0,
+            read_tcp_connection_stats(proc_root.path(), &[80])
+                .unwrap()
+                .time_wait
+        );
+    }
+
+    #[test]
+    fn test_read_tcp_connection_stats_missing_tcp() {
+        let proc_root = TestDir::new();
+
+        assert!(read_tcp_connection_stats(proc_root.path(), &[]).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use log::trace;
+
+/// Number of sockets per TCP state
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TcpStateCounts {
+    pub established: u64,
+    pub syn_sent: u64,
+    pub syn_recv: u64,
+    pub fin_wait1: u64,
+    pub fin_wait2: u64,
+    pub time_wait: u64,
+    pub close: u64,
+    pub close_wait: u64,
+    pub last_ack: u64,
+    pub listen: u64,
+    pub closing: u64,
+}
+
+impl TcpStateCounts {
+    /// Counts a socket by the hex state code used in /proc/net/tcp,
+    /// returns false for unknown states
+    fn count(&mut self, state: u8) -> bool {
+        let counter = match state {
+            0x01 => &mut self.established,
+            0x02 => &mut self.syn_sent,
+            // 0x0C is the request socket state NEW_SYN_RECV
+            0x03 | 0x0C => &mut self.syn_recv,
+            0x04 => &mut self.fin_wait1,
+            0x05 => &mut self.fin_wait2,
+            0x06 => &mut self.time_wait,
+            0x07 => &mut self.close,
+            0x08 => &mut self.close_wait,
+            0x09 => &mut self.last_ack,
+            0x0A => &mut self.listen,
+            0x0B => &mut self.closing,
+            _ => return false,
+        };
+
+        *counter += 1;
+
+        true
+    }
+}
+
+/// Adds the sockets listed in a /proc/net/tcp or /proc/net/tcp6 file to
+/// the counts, only sockets bound to one of the local ports are counted
+/// unless the port list is empty
+pub fn count_tcp_states(
+    path: &Path,
+    local_ports: &[u16],
+    counts: &mut TcpStateCounts,
+) -> anyhow::Result<()> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.to_string_lossy()))?;
+
+    parse_tcp_states(BufReader::new(file), local_ports, counts)
+}
+
+fn parse_tcp_states<R: BufRead>(
+    reader: R,
+    local_ports: &[u16],
+    counts: &mut TcpStateCounts,
+) -> anyhow::Result<()> {
+    // the first line is the header
+    for line in reader.lines().skip(1) {
+        let line = line?;
+        let mut fields = line.split_whitespace().skip(1);
+
+        let (local_address, state) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(local_address), Some(_remote_address), Some(state)) => (local_address, state),
+            _ => return Err(anyhow!("Invalid tcp socket line {}", line)),
+        };
+
+        if !local_ports.is_empty() {
+            let port = local_address
+                .rsplit(':')
+                .next()
+                .and_then(|port| u16::from_str_radix(port, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid local address {}", local_address))?;
+
+            if !local_ports.contains(&port) {
+                continue;
+            }
+        }
+
+        // newer kernels may add states, which mustn't fail the whole read
+        let state = u8::from_str_radix(state, 16)?;
+        if !counts.count(state) {
+            trace!("Skipping tcp socket with unknown state {:#04x}", state);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_NET_TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1001 1 0000000000000000 100 0 0 10 0
+   1: 00000000:01BB 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1002 1 0000000000000000 100 0 0 10 0
+   2: 0100007F:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1003 1 0000000000000000 100 0 0 10 0
+   3: 0A00000A:01BB 0B00000A:C350 01 00000000:00000000 02:00000A3B 00000000    33        0 1004 2 0000000000000000 20 4 30 10 -1
+   4: 0A00000A:01BB 0B00000A:C351 01 00000000:00000000 02:00000A3B 00000000    33        0 1005 2 0000000000000000 20 4 30 10 -1
+   5: 0A00000A:0050 0B00000A:C352 06 00000000:00000000 03:00001770 00000000     0        0 0 3 0000000000000000
+   6: 0A00000A:0016 0B00000A:C353 01 00000000:00000000 02:00000A3B 00000000     0        0 1006 2 0000000000000000 20 4 30 10 -1
+";
+
+    #[test]
+    fn test_parse_tcp_states() {
+        let mut counts = TcpStateCounts::default();
+        parse_tcp_states(PROC_NET_TCP.as_bytes(), &[], &mut counts).unwrap();
+
+        assert_eq!(
+            TcpStateCounts {
+                established: 3,
+                time_wait: 1,
+                listen: 3,
+                ..Default::default()
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_states_with_local_ports() {
+        let mut counts = TcpStateCounts::default();
+        parse_tcp_states(PROC_NET_TCP.as_bytes(), &[80, 443], &mut counts).unwrap();
+
+        assert_eq!(
+            TcpStateCounts {
+                established: 2,
+                time_wait: 1,
+                listen: 2,
+                ..Default::default()
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_states_with_single_local_port() {
+        let mut counts = TcpStateCounts::default();
+        parse_tcp_states(PROC_NET_TCP.as_bytes(), &[443], &mut counts).unwrap();
+
+        assert_eq!(
+            TcpStateCounts {
+                established: 2,
+                listen: 1,
+                ..Default::default()
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_states_accumulates() {
+        let mut counts = TcpStateCounts::default();
+        parse_tcp_states(PROC_NET_TCP.as_bytes(), &[22], &mut counts).unwrap();
+        parse_tcp_states(PROC_NET_TCP.as_bytes(), &[22], &mut counts).unwrap();
+
+        assert_eq!(
+            TcpStateCounts {
+                established: 2,
+                listen: 2,
+                ..Default::default()
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_states_skips_unknown_states() {
+        let mut counts = TcpStateCounts::default();
+        parse_tcp_states(
+            "header\n   0: 00000000:0050 00000000:0000 FF\n   1: 00000000:0050 00000000:0000 0A\n"
+                .as_bytes(),
+            &[],
+            &mut counts,
+        )
+        .unwrap();
+
+        assert_eq!(
+            TcpStateCounts {
+                listen: 1,
+                ..Default::default()
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn test_parse_tcp_states_invalid() {
+        let mut counts = TcpStateCounts::default();
+
+        assert!(
+            parse_tcp_states("header\n   0: 00000000:0050\n".as_bytes(), &[], &mut counts).is_err()
+        );
+        assert!(parse_tcp_states(
+            "header\n   0: 00000000:0050 00000000:0000 XY\n".as_bytes(),
+            &[],
+            &mut counts
+        )
+        .is_err());
+        assert!(parse_tcp_states(
+            "header\n   0: 00000000:zz 00000000:0000 01\n".as_bytes(),
+            &[80],
+            &mut counts
+        )
+        .is_err());
+    }
+}
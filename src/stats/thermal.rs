@@ -0,0 +1,231 @@
+mod sensors;
+mod throttle;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace};
+use tokio::sync::watch;
+use tokio::time;
+
+use super::{
+    NodeStats, NodeStatsDataSource, NodeStatsUpdateNotifier, NodeStatsUpdater, UpdateNotification,
+};
+use sensors::{read_hwmon_sensors, read_thermal_zones, SensorReading};
+use throttle::read_throttle_counts;
+
+pub use throttle::ThrottleCounts;
+
+/// Temperatures and throttling, the flags are set if any temperature
+/// reached the configured thresholds
+#[derive(Debug, Default, PartialEq)]
+pub struct ThermalStats {
+    pub temperatures: Vec<Temperature>,
+    pub throttle_counts: ThrottleCounts,
+    pub warning: bool,
+    pub critical: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Temperature {
+    pub label: String,
+    pub celsius: f64,
+    pub warning: bool,
+    pub critical: bool,
+}
+
+/// Temperatures in degrees Celsius at which readings get flagged
+#[derive(Debug, Default, Clone)]
+pub struct ThermalThresholds {
+    pub warning_celsius: Option<f64>,
+    pub critical_celsius: Option<f64>,
+}
+
+pub struct ThermalStatsProvider {
+    thermal_stats: Arc<RwLock<Arc<ThermalStats>>>,
+    update_receiver: watch::Receiver<UpdateNotification>,
+}
+
+impl ThermalStatsProvider {
+    pub fn new<T: Into<PathBuf>>(
+        sysfs_root: T,
+        thresholds: ThermalThresholds,
+        update_interval: Duration,
+    ) -> Self {
+        let shared_thermal_stats = Arc::new(RwLock::new(Arc::new(Default::default())));
+
+        let (tx, rx) = watch::channel(None);
+
+        let provider = Self {
+            thermal_stats: Arc::clone(&shared_thermal_stats),
+            update_receiver: rx,
+        };
+
+        start_update_loop(
+            Arc::downgrade(&shared_thermal_stats),
+            tx,
+            sysfs_root.into(),
+            thresholds,
+            update_interval,
+        );
+
+        provider
+    }
+
+    pub fn current_thermal_stats(&self) -> Arc<ThermalStats> {
+        Arc::clone(&self.thermal_stats.read().unwrap())
+    }
+}
+
+impl NodeStatsUpdater for ThermalStatsProvider {
+    fn update_node_stats(&self, mut node_stats: NodeStats) -> NodeStats {
+        node_stats.thermal = Some(self.current_thermal_stats());
+
+        node_stats
+    }
+}
+
+impl NodeStatsUpdateNotifier for ThermalStatsProvider {
+    fn get_update_channel_receiver(&self) -> watch::Receiver<UpdateNotification> {
+        self.update_receiver.clone()
+    }
+}
+
+impl NodeStatsDataSource for ThermalStatsProvider {
+    fn get_name(&self) -> &'static str {
+        "ThermalStatsProvider"
+    }
+}
+
+fn start_update_loop(
+    thermal_stats: Weak<RwLock<Arc<ThermalStats>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    sysfs_root: PathBuf,
+    thresholds: ThermalThresholds,
+    update_interval: Duration,
+) {
+    info!("Start ThermalStatsProvider update loop");
+
+    tokio::spawn(async move {
+        update_loop(
+            thermal_stats,
+            update_sender,
+            sysfs_root,
+            thresholds,
+            update_interval,
+        )
+        .await
+    });
+}
+
+async fn update_loop(
+    thermal_stats: Weak<RwLock<Arc<ThermalStats>>>,
+    update_sender: watch::Sender<UpdateNotification>,
+    sysfs_root: PathBuf,
+    thresholds: ThermalThresholds,
+    update_interval: Duration,
+) {
+    let mut interval = time::interval(update_interval);
+
+    interval.tick().await; // the first tick will complete immediately
+
+    loop {
+        let thermal_stats = match thermal_stats.upgrade() {
+            Some(thermal_stats) => thermal_stats,
+            None => {
+                info!("Couldn't get a reference to the thermal stats storage, ending update loop");
+                break;
+            }
+        };
+
+        let new_thermal_stats = read_thermal_stats(&sysfs_root, &thresholds);
+        trace!("Read thermal stats: {:?}", new_thermal_stats);
+        *thermal_stats.write().unwrap() = Arc::new(new_thermal_stats);
+        update_sender.broadcast(Some(SystemTime::now())).unwrap();
+
+        interval.tick().await;
+    }
+}
+
+fn read_thermal_stats(sysfs_root: &Path, thresholds: &ThermalThresholds) -> ThermalStats {
+    let readings = read_hwmon_sensors(sysfs_root)
+        .into_iter()
+        .chain(read_thermal_zones(sysfs_root));
+
+    let exceeds = |threshold: Option<f64>, celsius: f64| match threshold {
+        Some(threshold) => celsius >= threshold,
+        None => false,
+    };
+
+    let temperatures: Vec<Temperature> = readings
+        .map(|SensorReading { label, celsius }| Temperature {
+            label,
+            celsius,
+            warning: exceeds(thresholds.warning_celsius, celsius),
+            critical: exceeds(thresholds.critical_celsius, celsius),
+        })
+        .collect();
+
+    ThermalStats {
+        warning: temperatures.iter().any(|t| t.warning),
+        critical: temperatures.iter().any(|t| t.critical),
+        throttle_counts: read_throttle_counts(sysfs_root),
+        temperatures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_read_thermal_stats() {
+        let sysfs_root = TestDir::new();
+        sysfs_root.write_file("class/hwmon/hwmon0/name", "coretemp\n");
+        sysfs_root.write_file("class/hwmon/hwmon0/temp1_input", "85000\n");
+        sysfs_root.write_file("class/thermal/thermal_zone0/type", "acpitz\n");
+        sysfs_root.write_file("class/thermal/thermal_zone0/temp", "40000\n");
+        sysfs_root.write_file(
+            "devices/system/cpu/cpu0/thermal_throttle/core_throttle_count",
+            "2\n",
+        );
+
+        let thresholds = ThermalThresholds {
+            warning_celsius: Some(80.0),
+            critical_celsius: Some(95.0),
+        };
+
+        assert_eq!(
+            ThermalStats {
+                temperatures: vec![
+                    Temperature {
+                        label: "hwmon0/coretemp/temp1".into(),
+                        celsius: 85.0,
+                        warning: true,
+                        critical: false,
+                    },
+                    Temperature {
+                        label: "thermal_zone0/acpitz".into(),
+                        celsius: 40.0,
+                        warning: false,
+                        critical: false,
+                    },
+                ],
+                throttle_counts: ThrottleCounts {
+                    core: 2,
+                    package: 0,
+                },
+                warning: true,
+                critical: false,
+            },
+            read_thermal_stats(sysfs_root.path(), &thresholds)
+        );
+
+        let thermal_stats = read_thermal_stats(sysfs_root.path(), &Default::default());
+        assert!(!thermal_stats.warning);
+        assert!(!thermal_stats.critical);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::warn;
+
+/// A temperature sensor reading in degrees Celsius
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorReading {
+    pub label: String,
+    pub celsius: f64,
+}
+
+/// Reads the `temp*_input` files of all `<sysfs_root>/class/hwmon` devices,
+/// labelled by the device, its name and the sensor label if there is one.
+/// The names are not unique, e.g. every NVMe drive is named `nvme`.
+pub fn read_hwmon_sensors(sysfs_root: &Path) -> Vec<SensorReading> {
+    let mut readings = vec![];
+
+    for device_dir in list_dirs(&sysfs_root.join("class/hwmon")) {
+        let device_label = match read_trimmed(&device_dir.join("name")) {
+            Ok(name) => format!("{}/{}", file_name(&device_dir), name),
+            Err(_) => file_name(&device_dir),
+        };
+
+        let mut inputs: Vec<String> = match fs::read_dir(&device_dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("temp") && name.ends_with("_input"))
+                .collect(),
+            Err(_) => continue,
+        };
+        inputs.sort();
+
+        for input in inputs {
+            let sensor = input.trim_end_matches("_input");
+            let label = read_trimmed(&device_dir.join(format!("{}_label", sensor)))
+                .unwrap_or_else(|_| sensor.to_string());
+
+            match read_millidegrees(&device_dir.join(&input)) {
+                Ok(celsius) => readings.push(SensorReading {
+                    label: format!("{}/{}", device_label, label),
+                    celsius,
+                }),
+                // e.g. sensors of powered down devices fail with ENODATA
+                Err(e) => warn!("Failed to read hwmon sensor: {:?}", e),
+            }
+        }
+    }
+
+    readings
+}
+
+/// Reads the temperatures of all `<sysfs_root>/class/thermal` zones,
+/// labelled by the zone and its type
+pub fn read_thermal_zones(sysfs_root: &Path) -> Vec<SensorReading> {
+    list_dirs(&sysfs_root.join("class/thermal"))
+        .into_iter()
+        .filter(|zone_dir| file_name(zone_dir).starts_with("thermal_zone"))
+        .filter_map(|zone_dir| {
+            let zone_type = read_trimmed(&zone_dir.join("type")).ok()?;
+
+            match read_millidegrees(&zone_dir.join("temp")) {
+                Ok(celsius) => Some(SensorReading {
+                    label: format!("{}/{}", file_name(&zone_dir), zone_type),
+                    celsius,
+                }),
+                Err(e) => {
+                    warn!("Failed to read thermal zone: {:?}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Sorted subdirectories, the class entries are symlinks to the devices
+fn list_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => vec![],
+    };
+    dirs.sort();
+
+    dirs
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_trimmed(path: &Path) -> anyhow::Result<String> {
+    Ok(fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?
+        .trim()
+        .to_string())
+}
+
+fn read_millidegrees(path: &Path) -> anyhow::Result<f64> {
+    let millidegrees: i64 = read_trimmed(path)?
+        .parse()
+        .with_context(|| format!("Invalid temperature in {}", path.to_string_lossy()))?;
+
+    Ok(millidegrees as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_read_hwmon_sensors() {
+        let sysfs_root = TestDir::new();
+        sysfs_root.write_file("class/hwmon/hwmon0/name", "coretemp\n");
+        sysfs_root.write_file("class/hwmon/hwmon0/temp1_input", "45000\n");
+        sysfs_root.write_file("class/hwmon/hwmon0/temp1_label", "Package id 0\n");
+        sysfs_root.write_file("class/hwmon/hwmon0/temp2_input", "43500\n");
+        sysfs_root.write_file("class/hwmon/hwmon0/temp2_crit", "100000\n");
+        sysfs_root.write_file("class/hwmon/hwmon1/name", "nvme\n");
+        sysfs_root.write_file("class/hwmon/hwmon1/temp1_input", "38000\n");
+        sysfs_root.write_file("class/hwmon/hwmon1/temp1_label", "Composite\n");
+        sysfs_root.write_file("class/hwmon/hwmon2/name", "nvme\n");
+        sysfs_root.write_file("class/hwmon/hwmon2/temp1_input", "41000\n");
+        sysfs_root.write_file("class/hwmon/hwmon2/temp1_label", "Composite\n");
+        sysfs_root.write_file("class/hwmon/hwmon3/temp1_input", "garbage\n");
+
+        assert_eq!(
+            vec![
+                SensorReading {
+                    label: "hwmon0/coretemp/Package id 0".into(),
+                    celsius: 45.0,
+                },
+                SensorReading {
+                    label: "hwmon0/coretemp/temp2".into(),
+                    celsius: 43.5,
+                },
+                SensorReading {
+                    label: "hwmon1/nvme/Composite".into(),
+                    celsius: 38.0,
+                },
+                SensorReading {
+                    label: "hwmon2/nvme/Composite".into(),
+                    celsius: 41.0,
+                },
+            ],
+            read_hwmon_sensors(sysfs_root.path())
+        );
+    }
+
+    #[test]
+    fn test_read_thermal_zones() {
+        let sysfs_root = TestDir::new();
+        sysfs_root.write_file("class/thermal/thermal_zone0/type", "x86_pkg_temp\n");
+        sysfs_root.write_file("class/thermal/thermal_zone0/temp", "52000\n");
+        sysfs_root.write_file("class/thermal/cooling_device0/type", "Processor\n");
+
+        assert_eq!(
+            vec![SensorReading {
+                label: "thermal_zone0/x86_pkg_temp".into(),
+                celsius: 52.0,
+            }],
+            read_thermal_zones(sysfs_root.path())
+        );
+        assert!(read_thermal_zones(Path::new("invalid")).is_empty());
+    }
+}
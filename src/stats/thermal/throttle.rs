@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Cumulative number of thermal throttling events since boot
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ThrottleCounts {
+    /// Summed over all physical CPU cores
+    pub core: u64,
+    /// Summed over all physical packages
+    pub package: u64,
+}
+
+/// Reads the `thermal_throttle` counters of all CPUs below
+/// `<sysfs_root>/devices/system/cpu`, which are absent on non-x86 systems
+pub fn read_throttle_counts(sysfs_root: &Path) -> ThrottleCounts {
+    let entries = match fs::read_dir(sysfs_root.join("devices/system/cpu")) {
+        Ok(entries) => entries,
+        Err(_) => return Default::default(),
+    };
+
+    // the SMT siblings of a core report the same core counter
+    let mut cores = HashMap::new();
+    // every CPU of a package reports the same package counter
+    let mut packages = HashMap::new();
+
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("cpu") || !name[3..].chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+
+        let cpu_dir = entry.path();
+        let throttle_dir = cpu_dir.join("thermal_throttle");
+
+        let package_id = read_topology_id(&cpu_dir, "physical_package_id");
+
+        if let Some(core_count) = read_u64(&throttle_dir.join("core_throttle_count")) {
+            // without a topology every CPU is counted as its own core
+            let core_id = read_topology_id(&cpu_dir, "core_id").unwrap_or(name);
+
+            cores.insert((package_id.clone(), core_id), core_count);
+        }
+
+        if let Some(package_count) = read_u64(&throttle_dir.join("package_throttle_count")) {
+            packages.insert(package_id.unwrap_or_default(), package_count);
+        }
+    }
+
+    ThrottleCounts {
+        core: cores.values().sum(),
+        package: packages.values().sum(),
+    }
+}
+
+fn read_topology_id(cpu_dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(cpu_dir.join("topology").join(name))
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_read_throttle_counts() {
+        let sysfs_root = TestDir::new();
+        // cpu3 is the SMT sibling of cpu0, both packages have a core 0
+        for (cpu, package_id, core_id, core_count, package_count) in &[
+            (0, 0, 0, 3, 10),
+            (1, 0, 1, 4, 10),
+            (2, 1, 0, 5, 7),
+            (3, 0, 0, 3, 10),
+        ] {
+            let cpu_dir = format!("devices/system/cpu/cpu{}", cpu);
+            sysfs_root.write_file(
+                format!("{}/topology/physical_package_id", cpu_dir),
+                &format!("{}\n", package_id),
+            );
+            sysfs_root.write_file(
+                format!("{}/topology/core_id", cpu_dir),
+                &format!("{}\n", core_id),
+            );
+            sysfs_root.write_file(
+                format!("{}/thermal_throttle/core_throttle_count", cpu_dir),
+                &format!("{}\n", core_count),
+            );
+            sysfs_root.write_file(
+                format!("{}/thermal_throttle/package_throttle_count", cpu_dir),
+                &format!("{}\n", package_count),
+            );
+        }
+        sysfs_root.write_file("devices/system/cpu/cpufreq/boost", "1\n");
+
+        assert_eq!(
+            ThrottleCounts {
+                core: 12,
+                package: 17,
+            },
+            read_throttle_counts(sysfs_root.path())
+        );
+        assert_eq!(
+            ThrottleCounts::default(),
+            read_throttle_counts(Path::new("invalid"))
+        );
+    }
+}
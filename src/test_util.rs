@@ -0,0 +1,153 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use rand::{thread_rng, Rng};
+
+use crate::stats::bandwidth::{CounterSource, InterfaceCounters};
+
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new() -> TestDir {
+        let dirname: String = thread_rng()
+            .sample_iter(rand::distributions::Alphanumeric)
+            .take(8)
+            .collect();
+
+        let path = env::temp_dir().join(format!("nss-test-{}", dirname));
+        fs::create_dir_all(&path).unwrap();
+
+        TestDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, relative_path: P, content: &str) {
+        let file_path = self.path.join(relative_path);
+
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+
+        fs::write(file_path, content).unwrap();
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            eprintln!(
+                "Failed to remove tmp dir: {}\n{}",
+                self.path.to_string_lossy(),
+                e
+            );
+        }
+    }
+}
+
+/// Rates of mocked counters can be off by a bit due to the time passing
+/// between reading the counters and taking the timestamp
+pub fn is_approx_rate(expected: u64, actual: u64) -> bool {
+    let tolerance = expected / 100;
+
+    actual >= expected - tolerance && actual <= expected + tolerance
+}
+
+/// Returns the given counter values one after another, starting over at the end
+pub struct MockCounterSource {
+    rx_values: Vec<u64>,
+    tx_values: Vec<u64>,
+    rx_pos: AtomicUsize,
+    tx_pos: AtomicUsize,
+}
+
+impl MockCounterSource {
+    pub fn new(rx_values: Vec<u64>, tx_values: Vec<u64>) -> Self {
+        MockCounterSource {
+            rx_values,
+            tx_values,
+            rx_pos: Default::default(),
+            tx_pos: Default::default(),
+        }
+    }
+
+    fn next_value(values: &[u64], pos: &AtomicUsize) -> u64 {
+        let pos = pos.fetch_add(1, Ordering::SeqCst);
+
+        values[pos % values.len()]
+    }
+}
+
+impl CounterSource for MockCounterSource {
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters> {
+        Ok(InterfaceCounters {
+            rx_bytes: Self::next_value(&self.rx_values, &self.rx_pos),
+            tx_bytes: Self::next_value(&self.tx_values, &self.tx_pos),
+            ..Default::default()
+        })
+    }
+}
+
+/// Counts up with a constant rate in bytes per second, independent of
+/// how often the counters are read
+pub struct MockRateCounterSource {
+    start: Instant,
+    rx_rate: u64,
+    tx_rate: u64,
+}
+
+impl MockRateCounterSource {
+    pub fn new(rx_rate: u64, tx_rate: u64) -> Self {
+        MockRateCounterSource {
+            start: Instant::now(),
+            rx_rate,
+            tx_rate,
+        }
+    }
+
+    fn counter(&self, rate: u64) -> u64 {
+        (self.start.elapsed().as_nanos() * u128::from(rate) / 1_000_000_000) as u64
+    }
+}
+
+impl CounterSource for MockRateCounterSource {
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters> {
+        Ok(InterfaceCounters {
+            rx_bytes: self.counter(self.rx_rate),
+            tx_bytes: self.counter(self.tx_rate),
+            ..Default::default()
+        })
+    }
+}
+
+/// Fails every other read of the wrapped counter source
+pub struct FlakyCounterSource<T> {
+    source: T,
+    reads: AtomicUsize,
+}
+
+impl<T: CounterSource> FlakyCounterSource<T> {
+    pub fn new(source: T) -> Self {
+        FlakyCounterSource {
+            source,
+            reads: Default::default(),
+        }
+    }
+}
+
+impl<T: CounterSource> CounterSource for FlakyCounterSource<T> {
+    fn get_counters(&self) -> anyhow::Result<InterfaceCounters> {
+        if self.reads.fetch_add(1, Ordering::SeqCst) % 2 == 1 {
+            anyhow::bail!("Mock read failure");
+        }
+
+        self.source.get_counters()
+    }
+}